@@ -1,16 +1,21 @@
 use intersection::*;
 use board::*;
+use evaluator::RolloutEvaluator;
 use search::{Evaluate, Tree};
 
 /// JavaSriptコマンド用ワーカーです。
 pub struct JsClient {
     b: Board,
-    tree: Tree<NeuralNetwork>,
+    // WebDNNの評価結果を受け取れるようになるまではロールアウトで評価する
+    tree: Tree<RolloutEvaluator>,
 }
 
 impl JsClient {
-    pub fn new() -> Self {
-        let mut tree = Tree::new(NeuralNetwork {});
+    /// wasmではOSの乱数が使えないので、探索とロールアウトの乱数のシードseedを呼び出し元が渡します。
+    pub fn new(seed: u64) -> Self {
+        let mut evaluator = RolloutEvaluator::new(4);
+        evaluator.set_seed(seed);
+        let mut tree = Tree::with_seed(evaluator, seed);
         tree.set_time(0.0, 1.0);
         Self {
             b: Board::new(),
@@ -38,6 +43,7 @@ impl JsClient {
     }
 }
 
+#[allow(dead_code)]
 pub struct NeuralNetwork {}
 
 impl Evaluate for NeuralNetwork {
@@ -24,8 +24,10 @@ js_serializable!(MoveInfo);
 #[js_export]
 pub fn think(pv: Vec<usize>, playout: usize) -> MoveInfo {
     use std::f64;
+    use stdweb::unstable::TryInto;
 
-    let mut client = js_client::JsClient::new();
+    let seed: f64 = js! { return Math.random() * 4294967296; }.try_into().unwrap();
+    let mut client = js_client::JsClient::new(seed as u64);
     if client.load_pv(&pv).is_ok() {
         let result = client.best_move(playout);
         MoveInfo {
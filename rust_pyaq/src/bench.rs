@@ -0,0 +1,231 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use getopts;
+use test;
+use rand::{Rng, XorShiftRng};
+use serde_json;
+use constants::*;
+use coord_convert::rv2ev;
+use board::Board;
+use search::{Evaluate, Tree};
+use evaluator::UniformEvaluator;
+use engine_config::DEFAULT_MODEL;
+use utils;
+use neural_network::NeuralNetwork;
+use {add_log_opts, parse_args, parse_opt};
+
+/// ベンチマークの結果です。
+#[derive(Serialize)]
+struct BenchResult {
+    name: String,
+    unit: &'static str,
+    count: usize,
+    seconds: f32,
+    /// 毎秒の回数です。
+    rate: f32,
+}
+
+/// ベンチマークの結果の一覧です。
+/// 評価器はEvaluate::evaluateで1局面ずつ評価するので、バッチでの評価は計測しません。
+#[derive(Serialize)]
+struct BenchReport {
+    evaluator: String,
+    results: Vec<BenchResult>,
+}
+
+fn duration2float(d: Duration) -> f32 {
+    d.as_secs() as f32 + d.subsec_nanos() as f32 / 1000_000_000.0
+}
+
+/// duration秒経過するまでfを繰り返し実行し、fが返した回数の合計から毎秒の回数を求めます。
+fn measure<F: FnMut() -> usize>(
+    name: &str,
+    unit: &'static str,
+    duration: f32,
+    mut f: F,
+) -> BenchResult {
+    let start = Instant::now();
+    let mut count = 0;
+    let mut seconds;
+    loop {
+        count += f();
+        seconds = duration2float(start.elapsed());
+        if seconds >= duration {
+            break;
+        }
+    }
+    BenchResult {
+        name: name.to_string(),
+        unit: unit,
+        count: count,
+        seconds: seconds,
+        rate: count as f32 / seconds,
+    }
+}
+
+/// 両者が連続してパスするまでランダムに打った対局の着手の列を返します。
+fn random_game<R: Rng>(rng: &mut R) -> Vec<usize> {
+    let mut b = Board::new();
+    while b.get_move_cnt() < BVCNT * 2 {
+        let prev_move = b.get_prev_move();
+        let mov = b.random_play(rng);
+        if prev_move == PASS && mov == PASS {
+            break;
+        }
+    }
+    b.get_history().clone()
+}
+
+/// 碁盤の操作とロールアウトのベンチマークです。gamesはランダムな対局の着手の列です。
+fn bench_board(games: &[Vec<usize>], duration: f32, rng: &mut XorShiftRng) -> Vec<BenchResult> {
+    let mut results = Vec::new();
+    let mut b = Board::new();
+    let mut i = 0;
+    results.push(measure("Board::play", "moves", duration, || {
+        let game = &games[i % games.len()];
+        i += 1;
+        b.clear();
+        for &mov in game {
+            let _ = b.play(mov, false);
+        }
+        game.len()
+    }));
+    let mut i = 0;
+    results.push(measure("Board::legal", "calls", duration, || {
+        let game = &games[i % games.len()];
+        i += 1;
+        b.clear();
+        let mut cnt = 0;
+        for &mov in game {
+            for rv in 0..BVCNT {
+                if b.legal(rv2ev(rv)) {
+                    cnt += 1;
+                }
+            }
+            let _ = b.play(mov, false);
+        }
+        test::black_box(cnt);
+        game.len() * BVCNT
+    }));
+    results.push(measure("Board::random_play", "moves", duration, || {
+        random_game(rng).len()
+    }));
+    results.push(measure("Board::rollout", "rollouts", duration, || {
+        b.clear();
+        b.rollout(false, rng);
+        1
+    }));
+    results
+}
+
+/// 評価器nnの評価と、nnを使った探索のベンチマークです。
+fn bench_evaluator<T: Evaluate>(
+    nn: T,
+    games: &[Vec<usize>],
+    duration: f32,
+    playouts: usize,
+) -> Vec<BenchResult> {
+    let mut results = Vec::new();
+    let mut tree = Tree::new(nn);
+    // 各対局の序盤から中盤の局面を評価する
+    let positions: Vec<Board> = games
+        .iter()
+        .enumerate()
+        .map(|(i, game)| {
+            let mut b = Board::new();
+            for &mov in game.iter().take(i * BSIZE % BVCNT) {
+                let _ = b.play(mov, false);
+            }
+            b
+        })
+        .collect();
+    let mut i = 0;
+    results.push(measure("Evaluate::evaluate", "evals", duration, || {
+        test::black_box(tree.nn.evaluate(&positions[i % positions.len()]));
+        i += 1;
+        1
+    }));
+    // 打ち切らずに指定のプレイアウト数を探索させる
+    tree.config_mut().early_stop = false;
+    tree.set_max_playout(playouts);
+    results.push(measure("Tree::search", "playouts", duration, || {
+        tree.clear();
+        let _ = tree.search(&Board::new(), 0.0, false, false);
+        tree.playout_cnt()
+    }));
+    results
+}
+
+/// 結果の一覧を表にして表示します。
+fn print_table(report: &BenchReport) {
+    println!(
+        "evaluator: {} (one position per evaluation, batching is not measured)",
+        report.evaluator
+    );
+    println!("|{:20}|{:>12}|{:>9}|{:>20}|", "benchmark", "count", "seconds", "rate");
+    println!("|{:-<20}|{:->12}|{:->9}|{:->20}|", "", "", "", "");
+    for r in &report.results {
+        println!(
+            "|{:20}|{:>12}|{:>9.2}|{:>20}|",
+            r.name,
+            r.count,
+            r.seconds,
+            format!("{:.0} {}/s", r.rate, r.unit)
+        );
+    }
+}
+
+/// benchサブコマンドを実行します。
+pub fn bench_main(program: &str, args: &[String]) {
+    let mut opts = getopts::Options::new();
+    opts.optflag("h", "help", "Help")
+        .optopt("", "model", "Model file. default: frozen_model.pb", "FILE")
+        .optflag("", "stub", "Use the uniform stub evaluator even if the model exists.")
+        .optopt("", "seconds", "Duration of each benchmark. default: 1", "NUM")
+        .optopt("", "playouts", "Playouts per search. default: 1600", "NUM")
+        .optopt("", "seed", "Random seed. default: 0", "NUM")
+        .optopt("", "json", "Write the results as JSON to this file. - writes stdout.", "FILE");
+    add_log_opts(&mut opts);
+    let brief = format!(
+        "Usage: {} bench [options]\n\n\
+         Measures board operations, rollouts, evaluations and search playouts per second. \
+         The uniform stub evaluator is used if the model file is not found.",
+        program
+    );
+    let matches = parse_args(&opts, args, &brief);
+    let model = matches.opt_str("model").unwrap_or(DEFAULT_MODEL.to_string());
+    let duration = parse_opt(&matches, "seconds", 1.0);
+    let playouts = parse_opt(&matches, "playouts", 1600);
+    let mut rng = utils::seeded_rng(parse_opt(&matches, "seed", 0));
+    let games: Vec<Vec<usize>> = (0..16).map(|_| random_game(&mut rng)).collect();
+
+    let mut results = bench_board(&games, duration, &mut rng);
+    let use_model = !matches.opt_present("stub") && Path::new(&model).exists();
+    let evaluator = if use_model {
+        results.extend(bench_evaluator(NeuralNetwork::new(&model), &games, duration, playouts));
+        format!("tensorflow ({})", model)
+    } else {
+        results.extend(bench_evaluator(UniformEvaluator, &games, duration, playouts));
+        "uniform stub".to_string()
+    };
+    let report = BenchReport {
+        evaluator: evaluator,
+        results: results,
+    };
+    match matches.opt_str("json") {
+        Some(ref name) if name == "-" => {
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        }
+        Some(name) => {
+            print_table(&report);
+            let json = serde_json::to_string_pretty(&report).unwrap();
+            if let Err(e) = File::create(&name).and_then(|mut f| f.write_all(json.as_bytes())) {
+                eprintln!("--json: {}: {}", name, e);
+                std::process::exit(1);
+            }
+        }
+        None => print_table(&report),
+    }
+}
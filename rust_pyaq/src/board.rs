@@ -1,4 +1,5 @@
 use std::hash::{Hash, Hasher};
+use rand::Rng;
 use constants::*;
 use intersection::*;
 use coord_convert::*;
@@ -41,6 +42,19 @@ pub struct Board {
     history: Vec<usize>,
 }
 
+/// 着手の石の取り合いに関する特徴です。Board::tacticsが返します。
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Tactics {
+    /// 取れる相手の石の数です。
+    pub capture_cnt: usize,
+    /// アタリにする相手の連の数です。
+    pub atari_cnt: usize,
+    /// アタリにされている自分の連がダメ2つ以上になるかどうかです。
+    pub saves_atari: bool,
+    /// 相手の石を取らずに自分の連がアタリになるかどうかです。
+    pub self_atari: bool,
+}
+
 pub struct Candidates {
     pub hash: u64,
     pub move_cnt: usize,
@@ -84,11 +98,104 @@ impl Board {
         self.prev_move
     }
 
+    #[inline]
+    pub fn get_turn(&self) -> Color {
+        self.turn
+    }
+
     #[inline]
     pub fn get_history(&self) -> &Vec<usize> {
         &self.history
     }
 
+    /// 拡張碁盤の線形座標vの点の状態を返します。
+    #[inline]
+    pub fn get_state(&self, v: usize) -> Intersection {
+        self.state[v]
+    }
+
+    /// i手前の局面の、拡張碁盤の線形座標vの点の状態を返します。0が直前の局面です。
+    #[inline]
+    pub fn get_prev_state(&self, i: usize, v: usize) -> Intersection {
+        self.prev_state[i][v]
+    }
+
+    /// コウで着手できない点を返します。なければVNULLです。
+    #[inline]
+    pub fn get_ko(&self) -> usize {
+        self.ko
+    }
+
+    /// 盤上の状態state、直前の局面の状態prev_state(新しい順)、コウの点ko、手番turnから局面を作ります。
+    /// 連は盤上の石から作り直します。手数は着手の履歴historyの長さで、直前の着手はその最後の着手です。
+    pub fn from_raw(
+        state: &[Intersection; EBVCNT],
+        prev_state: &[[Intersection; EBVCNT]; KEEP_PREV_CNT],
+        ko: usize,
+        turn: Color,
+        history: Vec<usize>,
+    ) -> Result<Self, String> {
+        let mut b = Self::new();
+        for rv in 0..BVCNT {
+            let v = rv2ev(rv);
+            b.turn = match state[v] {
+                Intersection::Stone(color) => color,
+                _ => continue,
+            };
+            b.place_stone(v);
+            if b.remove_cnt > 0 {
+                return Err(format!("stones without liberties near {}", ev2str(v)));
+            }
+        }
+        for rv in 0..BVCNT {
+            let v = rv2ev(rv);
+            if b.state[v] != Intersection::Empty && b.sg[b.id[v]].get_lib_cnt() == 0 {
+                return Err(format!("stones without liberties at {}", ev2str(v)));
+            }
+        }
+        b.prev_state = *prev_state;
+        b.ko = ko;
+        b.turn = turn;
+        b.move_cnt = history.len();
+        b.prev_move = history.last().cloned().unwrap_or(VNULL);
+        b.history = history;
+        b.remove_cnt = 0;
+        Ok(b)
+    }
+
+    /// showboardと同じ形式の盤面図diagramから、手番turnの局面を作ります。
+    /// Xが黒石、Oが白石、.と+が空点で、それ以外の文字は無視します。
+    /// 着手の履歴はなく、直前の局面は現局面と同じとみなします。
+    pub fn from_diagram(diagram: &str, turn: Color) -> Result<Self, String> {
+        let rows: Vec<Vec<char>> = diagram
+            .lines()
+            .map(|line| {
+                line.chars()
+                    .filter(|&c| c == 'X' || c == 'O' || c == '.' || c == '+')
+                    .collect::<Vec<_>>()
+            })
+            .filter(|row| !row.is_empty())
+            .collect();
+        if rows.len() != BSIZE {
+            return Err(format!("diagram has {} rows instead of {}", rows.len(), BSIZE));
+        }
+        let mut state = Self::new().state;
+        for (i, row) in rows.iter().enumerate() {
+            let y = (BSIZE - i) as u8;
+            if row.len() != BSIZE {
+                return Err(format!("row {} has {} points instead of {}", y, row.len(), BSIZE));
+            }
+            for (x, &c) in row.iter().enumerate() {
+                state[xy2ev(x as u8 + 1, y)] = match c {
+                    'X' => Intersection::Stone(Color::Black),
+                    'O' => Intersection::Stone(Color::White),
+                    _ => Intersection::Empty,
+                };
+            }
+        }
+        Self::from_raw(&state, &[state; KEEP_PREV_CNT], VNULL, turn, Vec::new())
+    }
+
     pub fn clear(&mut self) {
         for x in 1..(BSIZE + 1) as u8 {
             for y in 1..(BSIZE + 1) as u8 {
@@ -126,6 +233,7 @@ impl Board {
         dest.ko = self.ko;
         dest.turn = self.turn;
         dest.move_cnt = self.move_cnt;
+        dest.prev_move = self.prev_move;
         dest.remove_cnt = self.remove_cnt;
         dest.history = self.history.clone();
     }
@@ -305,9 +413,8 @@ impl Board {
     }
 
     /// 眼を埋めないようにランダムプレイします。
-    pub fn random_play(&mut self) -> usize {
-        use rand::{thread_rng, Rng};
-
+    /// 乱数は引数rngから取るので、シード付きの乱数生成器を渡せば再現性があります。
+    pub fn random_play<R: Rng>(&mut self, rng: &mut R) -> usize {
         let mut empty_list: Vec<usize> = self.state
             .iter()
             .enumerate()
@@ -319,7 +426,6 @@ impl Board {
                 }
             })
             .collect();
-        let mut rng = thread_rng();
         rng.shuffle(&mut empty_list);
         for &v in &empty_list {
             if let Ok(_) = self.play(v, true) {
@@ -355,15 +461,57 @@ impl Board {
         (stone_cnt[1] - stone_cnt[0]) as f32 - KOMI
     }
 
+    /// 現局面を終局図として、Tromp-Taylorルールで数えた黒から見たスコアを返します。
+    /// 盤上の石の数と、一方の色の石のみに到達する空点の領域の大きさの和がその色の地です。
+    pub fn area_score(&self) -> f32 {
+        let mut stone_cnt = [0, 0];
+        let mut visited = [false; EBVCNT];
+        for v in (0..BVCNT).map(rv2ev) {
+            match self.state[v] {
+                Intersection::Stone(c) => stone_cnt[c as usize] += 1,
+                Intersection::Empty if !visited[v] => {
+                    let mut region_size = 0;
+                    let mut reach = [false, false];
+                    let mut stack = vec![v];
+                    visited[v] = true;
+                    while let Some(v) = stack.pop() {
+                        region_size += 1;
+                        for &nv in &neighbors(v) {
+                            match self.state[nv] {
+                                Intersection::Stone(c) => reach[c as usize] = true,
+                                Intersection::Empty if !visited[nv] => {
+                                    visited[nv] = true;
+                                    stack.push(nv);
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                    for &c in &[Color::White, Color::Black] {
+                        if reach[c as usize] && !reach[c.opponent() as usize] {
+                            stone_cnt[c as usize] += region_size;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        (stone_cnt[Color::Black as usize] - stone_cnt[Color::White as usize]) as f32 - KOMI
+    }
+
     /// 原始モンテカルロでロールアウトします。
     /// 死に石すべてを上げて十分に陣地を埋めるのに使います。
-    pub fn rollout(&mut self, show_board: bool) {
+    pub fn rollout<R: Rng>(&mut self, show_board: bool, rng: &mut R) {
         while self.move_cnt < EBVCNT * 2 {
             let prev_move = self.prev_move;
-            let mov = self.random_play();
+            let mov = self.random_play(rng);
             if show_board && mov != PASS {
-                eprintln!("\nmove count={}", self.move_cnt);
-                self.showboard();
+                debug!(
+                    target: "board",
+                    "\nmove count={}\n{}\n",
+                    self.move_cnt,
+                    self.to_diagram()
+                );
             }
             if prev_move == PASS && mov == PASS {
                 break;
@@ -371,15 +519,16 @@ impl Board {
         }
     }
 
-    pub fn showboard(&self) {
-        fn print_xlabel() {
+    /// 盤面図を返します。直前の着手は[]で囲みます。
+    pub fn to_diagram(&self) -> String {
+        fn xlabel() -> String {
             let mut line_str = "  ".to_string();
             for x in 1..BSIZE + 1 {
                 line_str.push_str(&format!(" {} ", X_LABELS[x]));
             }
-            eprintln!("{}", line_str);
+            line_str
         }
-        print_xlabel();
+        let mut lines = vec![xlabel()];
         for y in (1..(BSIZE + 1) as u8).rev() {
             let mut line_str = format!("{:>2}", y);
             for x in 1..(BSIZE + 1) as u8 {
@@ -402,10 +551,15 @@ impl Board {
                 line_str.push_str(&x_str);
             }
             line_str.push_str(&format!("{:>2}", y));
-            eprintln!("{}", line_str);
+            lines.push(line_str);
         }
-        print_xlabel();
-        eprintln!("");
+        lines.push(xlabel());
+        lines.join("\n")
+    }
+
+    /// 盤面図をターゲットboardのinfoレベルでログに出力します。
+    pub fn showboard(&self) {
+        info!(target: "board", "{}\n", self.to_diagram());
     }
 
     /// 与えられたsliceにニューラルネットワークへの入力を代入します。
@@ -463,6 +617,56 @@ impl Board {
         (h1 ^ h2) ^ self.turn as u64
     }
 
+    /// ニューラルネットワークへの入力を決める局面のキーを返します。
+    /// hashと違い、put_featuresが使う履歴をすべて含みます。
+    pub fn position_key(&self) -> u64 {
+        use std::collections::hash_map::DefaultHasher;
+        let mut hasher = DefaultHasher::new();
+        self.state.hash(&mut hasher);
+        self.prev_state.hash(&mut hasher);
+        self.turn.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// 手番の着手vの石の取り合いに関する特徴を返します。vは合法手とします。
+    pub fn tactics(&self, v: usize) -> Tactics {
+        use std::collections::HashSet;
+
+        let mut tactics = Tactics::default();
+        if v == PASS {
+            return tactics;
+        }
+        let my = Intersection::Stone(self.turn);
+        let mut libs = HashSet::new(); // 着手後の連のダメ
+        let mut in_atari = false;
+        let mut visited_ids = Vec::with_capacity(4);
+        for &nv in &neighbors(v) {
+            match self.state[nv] {
+                Intersection::Empty => {
+                    libs.insert(nv);
+                }
+                Intersection::Stone(_) if !visited_ids.contains(&self.id[nv]) => {
+                    visited_ids.push(self.id[nv]);
+                    let sg = &self.sg[self.id[nv]];
+                    if self.state[nv] == my {
+                        in_atari |= sg.get_lib_cnt() == 1;
+                        libs.extend(sg.get_libs().iter().cloned());
+                    } else if sg.get_lib_cnt() == 1 {
+                        tactics.capture_cnt += sg.get_size();
+                        libs.insert(nv); // 取った石の跡はダメになる
+                    } else if sg.get_lib_cnt() == 2 {
+                        tactics.atari_cnt += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        libs.remove(&v);
+        tactics.saves_atari = in_atari && libs.len() >= 2;
+        tactics.self_atari = libs.len() == 1 && tactics.capture_cnt == 0;
+        tactics
+    }
+
     /// 局面の情報を返します。
     pub fn candidates(&self) -> Candidates {
         let mut cand_list: Vec<usize> = self.state
@@ -486,7 +690,7 @@ impl Board {
 
     /// ランダムロールアウトを実行してスコアを返します。
     /// 現局面が終局図と仮定して統計的にスコアを算出します。
-    pub fn final_score(&self) -> f32 {
+    pub fn final_score<R: Rng>(&self, rng: &mut R) -> f32 {
         use utils;
 
         const ROLL_OUT_NUM: usize = 256;
@@ -495,9 +699,108 @@ impl Board {
 
         for _ in 0..ROLL_OUT_NUM {
             self.copy_to(&mut b_cpy);
-            b_cpy.rollout(false);
+            b_cpy.rollout(false, rng);
             double_score_list.push((b_cpy.score() * 2.0) as i32);
         }
         *utils::most_common(&double_score_list) as f32 / 2.0
     }
 }
+
+#[test]
+fn test_area_score() {
+    let mut b = Board::new();
+    assert_eq!(b.area_score(), -KOMI);
+    let _ = b.play(xy2ev(5, 5), false);
+    assert_eq!(b.area_score(), BVCNT as f32 - KOMI);
+    let _ = b.play(xy2ev(1, 1), false);
+    assert_eq!(b.area_score(), 1.0 - 1.0 - KOMI);
+}
+
+#[test]
+fn test_rollout_reproducible() {
+    use utils::seeded_rng;
+
+    let mut b1 = Board::new();
+    let mut b2 = Board::new();
+    b1.rollout(false, &mut seeded_rng(7));
+    b2.rollout(false, &mut seeded_rng(7));
+    assert_eq!(b1.get_history(), b2.get_history());
+    assert_eq!(
+        b1.final_score(&mut seeded_rng(7)),
+        b2.final_score(&mut seeded_rng(7))
+    );
+}
+
+#[test]
+fn test_position_key() {
+    let v = xy2ev(3, 3);
+    let mut b1 = Board::new();
+    b1.play_sequence([v, PASS, PASS, PASS].iter().cloned());
+    let mut b2 = Board::new();
+    b2.play_sequence([PASS, PASS, v, PASS].iter().cloned());
+    assert_eq!(b1.hash(), b2.hash());
+    assert!(b1.position_key() != b2.position_key()); // 2手前の局面が違う
+    let mut b3 = Board::new();
+    b1.copy_to(&mut b3);
+    assert_eq!(b1.position_key(), b3.position_key());
+}
+
+#[test]
+fn test_from_diagram() {
+    let mut b1 = Board::new();
+    for &(x, y) in &[(3, 3), (7, 7), (3, 7)] {
+        let _ = b1.play(xy2ev(x, y), false);
+    }
+    let diagram = "
+        A  B  C  D  E  F  G  H  J
+     9 .  .  .  .  .  .  .  .  .  9
+     8 .  .  .  .  .  .  .  .  .  8
+     7 .  . [X] .  .  .  O  .  .  7
+     6 .  .  .  .  .  .  .  .  .  6
+     5 .  .  .  .  +  .  .  .  .  5
+     4 .  .  .  .  .  .  .  .  .  4
+     3 .  .  X  .  .  .  .  .  .  3
+     2 .  .  .  .  .  .  .  .  .  2
+     1 .  .  .  .  .  .  .  .  .  1
+        A  B  C  D  E  F  G  H  J
+    ";
+    let b2 = Board::from_diagram(diagram, Color::White).unwrap();
+    let b3 = Board::from_diagram(&b1.to_diagram(), Color::White).unwrap();
+    assert_eq!(b3.to_diagram(), b1.to_diagram().replace("[X]", " X "));
+    assert!(b1.state[..] == b2.state[..]);
+    assert!(b2.get_turn() == Color::White);
+    assert!(b2.get_state(xy2ev(7, 7)) == Intersection::Stone(Color::White));
+    assert!(!b2.legal(xy2ev(3, 3)));
+
+    assert!(Board::from_diagram(".........\n", Color::Black).is_err());
+    let captured = format!("XO.......\nO........\n{}", ".........\n".repeat(BSIZE - 2));
+    assert!(Board::from_diagram(&captured, Color::Black).is_err());
+}
+
+#[test]
+fn test_tactics() {
+    let diagram = "
+        . . . . . . . . .
+        . . . . . . . . .
+        . . . . . . . . .
+        . . . . . . . . .
+        . . . . . . . . .
+        . . X . . . . . .
+        . X O . . . . . .
+        X O . . . . . . .
+        . X . . . . . . .
+    ";
+    let b = Board::from_diagram(diagram, Color::White).unwrap();
+    // 白B2はアタリで、C2に逃げるとダメが増える
+    let t = b.tactics(xy2ev(3, 2));
+    assert!(t.saves_atari && !t.self_atari);
+    // 白A3は黒を取れずに自分がアタリになる
+    assert!(b.tactics(xy2ev(1, 3)).self_atari);
+
+    let b = Board::from_diagram(diagram, Color::Black).unwrap();
+    // 黒C2で白B2を取る
+    assert_eq!(b.tactics(xy2ev(3, 2)).capture_cnt, 1);
+    // 黒D3で白C3をアタリにする
+    assert_eq!(b.tactics(xy2ev(4, 3)).atari_cnt, 1);
+    assert_eq!(b.tactics(PASS), Tactics::default());
+}
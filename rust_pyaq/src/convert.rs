@@ -0,0 +1,196 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use sgf::SgfCollection;
+use constants::*;
+use intersection::Color;
+use coord_convert::xy2ev;
+use board::Board;
+use symmetry::*;
+use training_data::*;
+use gtp::read_file;
+
+/// dir以下のSGFファイルのパスを再帰的に集めます。
+fn collect_sgf_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_sgf_files(&path, files)?;
+        } else if path.extension()
+            .and_then(|e| e.to_str())
+            .map_or(false, |e| e.to_lowercase() == "sgf")
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// SGFのREプロパティから勝者を返します。持碁の場合はNoneです。
+fn parse_result(re: &str) -> Result<Option<Color>, String> {
+    let re = re.trim();
+    if re.starts_with("B+") {
+        Ok(Some(Color::Black))
+    } else if re.starts_with("W+") {
+        Ok(Some(Color::White))
+    } else if re == "0" || re == "Draw" || re == "Jigo" {
+        Ok(None)
+    } else {
+        Err(format!("unknown result \"{}\"", re))
+    }
+}
+
+/// SGFテキストを解析します。ゲームツリーが1つもない場合もエラーを返します。
+pub fn parse_sgf(sgf: &str) -> Result<SgfCollection, String> {
+    // SgfCollectionは添字でしか参照できないので、ゲームツリーの有無はテキストで判断する
+    if !sgf.contains('(') {
+        return Err("no game".to_string());
+    }
+    SgfCollection::from_sgf(sgf).map_err(|_| "invalid sgf".to_string())
+}
+
+/// SGFの座標の文字列を(x, y)に変換します。2文字でない、碁盤の外を指すなど不正な座標の時はNoneを返します。
+fn parse_point(point: &str) -> Option<(u8, u8)> {
+    let coord = |c: u8| {
+        if c >= b'a' && c - b'a' < BSIZE as u8 {
+            Some(c - b'a' + 1)
+        } else {
+            None
+        }
+    };
+    match point.as_bytes() {
+        &[x, y] => Some((coord(x)?, coord(y)?)),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_parse_point() {
+    assert_eq!(parse_point("ab"), Some((1, 2)));
+    assert_eq!(parse_point("ii"), Some((9, 9)));
+    assert_eq!(parse_point("ja"), None);
+    assert_eq!(parse_point("Ab"), None);
+    assert_eq!(parse_point("a"), None);
+    assert_eq!(parse_point("abc"), None);
+    assert_eq!(parse_point("äb"), None);
+}
+
+/// SGFの本譜の着手の列を返します。
+/// 碁盤のサイズが違う、置石がある、手番が交互でないなど、初期局面から再生できない棋譜の場合はその理由を返します。
+pub fn main_line(collection: &SgfCollection) -> Result<Vec<usize>, String> {
+    let root = &collection[0];
+    if let Ok(size) = root.get_number("SZ") {
+        if size as usize != BSIZE {
+            return Err(format!("board size {}", size));
+        }
+    }
+    if root.get_point("AB").is_ok() || root.get_point("AW").is_ok() {
+        return Err("setup stones".to_string());
+    }
+
+    let mut moves = Vec::new();
+    let mut node = root;
+    while node.children.len() > 0 {
+        node = &node.children[0];
+        let (color, point) = if let Ok(point) = node.get_point("B") {
+            (Color::Black, point)
+        } else if let Ok(point) = node.get_point("W") {
+            (Color::White, point)
+        } else {
+            continue;
+        };
+        let move_number = moves.len() + 1;
+        let turn = if moves.len() % 2 == 0 {
+            Color::Black
+        } else {
+            Color::White
+        };
+        if color != turn {
+            return Err(format!("unexpected color at move {}", move_number));
+        }
+        let mov = if point.is_empty() || &point[..] == "tt" {
+            PASS
+        } else {
+            match parse_point(&point) {
+                Some((x, y)) => xy2ev(x, y),
+                None => return Err(format!("invalid point \"{}\" at move {}", point, move_number)),
+            }
+        };
+        moves.push(mov);
+    }
+    Ok(moves)
+}
+
+/// SGFテキストの本譜を再生し、各局面の記録と勝者を返します。
+/// 学習データにできない棋譜の場合はその理由を返します。
+fn replay(sgf: &str) -> Result<(Vec<PositionRecord>, Option<Color>), String> {
+    let collection = parse_sgf(sgf)?;
+    let moves = main_line(&collection)?;
+    let winner = match collection[0].get_text("RE") {
+        Ok(re) => parse_result(&re)?,
+        Err(_) => return Err("no result".to_string()),
+    };
+
+    let mut b = Board::new();
+    let mut records = Vec::new();
+    for (i, &mov) in moves.iter().enumerate() {
+        records.push(PositionRecord::new(&b, one_hot(mov)));
+        if b.play(mov, false).is_err() {
+            return Err(format!("illegal move at move {}", i + 1));
+        }
+    }
+    if records.is_empty() {
+        return Err("no moves".to_string());
+    }
+    Ok((records, winner))
+}
+
+/// dir以下のSGFファイルを再生して(入力フィーチャー, 着手, 対局結果)の学習データに変換し、
+/// out_dirにチャンクファイルとして書き出します。
+/// augmentの時は8通りの対称変換で水増しします。変換できなかった棋譜は理由とともに報告します。
+pub fn convert(dir: &Path, out_dir: &Path, augment: bool, chunk_size: usize) -> io::Result<()> {
+    let mut files = Vec::new();
+    collect_sgf_files(dir, &mut files)?;
+    files.sort();
+
+    let mut writer = ChunkWriter::new(out_dir, "sgf", chunk_size)?;
+    let syms = if augment { SYMMETRY_CNT } else { 1 };
+    let mut game_cnt = 0;
+    let mut sample_cnt = 0;
+    let mut skipped_cnt = 0;
+    for path in &files {
+        let result = read_file(&path.to_string_lossy())
+            .map_err(|e| e.to_string())
+            .and_then(|sgf| replay(&sgf));
+        match result {
+            Ok((records, winner)) => {
+                for record in records {
+                    let sample = record.into_sample(winner);
+                    for sym in 0..syms {
+                        writer.push(Sample {
+                            features: transform_features(&sample.features, sym),
+                            policy: transform_policy(&sample.policy, sym),
+                            value: sample.value,
+                        })?;
+                        sample_cnt += 1;
+                    }
+                }
+                game_cnt += 1;
+            }
+            Err(reason) => {
+                warn!(target: "convert", "skipped {}: {}", path.display(), reason);
+                skipped_cnt += 1;
+            }
+        }
+    }
+    writer.flush()?;
+    info!(
+        target: "convert",
+        "converted {} games into {} samples ({} chunks), skipped {} games",
+        game_cnt,
+        sample_cnt,
+        writer.get_chunk_cnt(),
+        skipped_cnt
+    );
+    Ok(())
+}
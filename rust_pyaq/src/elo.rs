@@ -0,0 +1,187 @@
+//! 対局の勝敗からの勝率、Elo差の推定と逐次確率比検定(SPRT)を提供します。
+
+/// スコア(勝ちを1、持碁を0.5とした得点率)scoreに対応するElo差を返します。
+pub fn elo_from_score(score: f32) -> f32 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+/// Elo差eloに対応するスコアを返します。
+pub fn score_from_elo(elo: f32) -> f32 {
+    1.0 / (1.0 + 10.0f32.powf(-elo / 400.0))
+}
+
+/// 一方から見た対局の勝敗の集計です。
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MatchStats {
+    pub wins: usize,
+    pub losses: usize,
+    pub draws: usize,
+}
+
+impl MatchStats {
+    /// 結果を追加します。winは勝ちならSome(true)、負けならSome(false)、持碁ならNoneです。
+    pub fn add(&mut self, win: Option<bool>) {
+        match win {
+            Some(true) => self.wins += 1,
+            Some(false) => self.losses += 1,
+            None => self.draws += 1,
+        }
+    }
+
+    pub fn game_cnt(&self) -> usize {
+        self.wins + self.losses + self.draws
+    }
+
+    /// スコアを返します。対局がなければ0.5です。
+    pub fn score(&self) -> f32 {
+        if self.game_cnt() == 0 {
+            return 0.5;
+        }
+        (self.wins as f32 + self.draws as f32 * 0.5) / self.game_cnt() as f32
+    }
+
+    /// 1局あたりのスコアの分散を返します。
+    fn variance(&self) -> f32 {
+        let n = self.game_cnt();
+        if n == 0 {
+            return 0.0;
+        }
+        let s = self.score();
+        (self.wins as f32 * (1.0 - s).powi(2) + self.draws as f32 * (0.5 - s).powi(2)
+            + self.losses as f32 * s.powi(2)) / n as f32
+    }
+
+    /// スコアの信頼区間を返します。zは標準誤差の何倍の幅を取るかで、95%なら1.96です。
+    pub fn score_interval(&self, z: f32) -> (f32, f32) {
+        let n = self.game_cnt().max(1) as f32;
+        let margin = z * (self.variance() / n).sqrt();
+        let s = self.score();
+        ((s - margin).max(0.0), (s + margin).min(1.0))
+    }
+
+    /// Elo差の推定値を返します。全勝、全敗の場合は無限大です。
+    pub fn elo(&self) -> f32 {
+        elo_from_score(self.score())
+    }
+
+    /// Elo差の信頼区間を返します。
+    pub fn elo_interval(&self, z: f32) -> (f32, f32) {
+        let (low, high) = self.score_interval(z);
+        (elo_from_score(low), elo_from_score(high))
+    }
+}
+
+/// SPRTの判定です。
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SprtResult {
+    /// 判定にはまだ対局が足りません。
+    Continue,
+    /// 帰無仮説(Elo差がelo0)を採択しました。
+    AcceptH0,
+    /// 対立仮説(Elo差がelo1)を採択しました。
+    AcceptH1,
+}
+
+/// Elo差がelo0かelo1かを判定する逐次確率比検定です。
+/// 持碁を半分の勝ちと半分の負けとして、各対局をスコアのベルヌーイ試行とみなして対数尤度比を求めます。
+/// 分散を使わないので、全勝や全敗でも判定できます。
+#[derive(Clone, Copy, Debug)]
+pub struct Sprt {
+    pub elo0: f32,
+    pub elo1: f32,
+    /// 第1種の過誤の確率です。
+    pub alpha: f32,
+    /// 第2種の過誤の確率です。
+    pub beta: f32,
+}
+
+impl Sprt {
+    pub fn new(elo0: f32, elo1: f32, alpha: f32, beta: f32) -> Self {
+        Self {
+            elo0: elo0,
+            elo1: elo1,
+            alpha: alpha,
+            beta: beta,
+        }
+    }
+
+    /// 対数尤度比の下限と上限を返します。下限を下回るとH0、上限を上回るとH1を採択します。
+    pub fn bounds(&self) -> (f32, f32) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    /// statsの対数尤度比を返します。
+    pub fn llr(&self, stats: &MatchStats) -> f32 {
+        let s0 = score_from_elo(self.elo0);
+        let s1 = score_from_elo(self.elo1);
+        let wins = stats.wins as f32 + stats.draws as f32 * 0.5;
+        let losses = stats.losses as f32 + stats.draws as f32 * 0.5;
+        wins * (s1 / s0).ln() + losses * ((1.0 - s1) / (1.0 - s0)).ln()
+    }
+
+    pub fn judge(&self, stats: &MatchStats) -> SprtResult {
+        let llr = self.llr(stats);
+        let (lower, upper) = self.bounds();
+        if llr <= lower {
+            SprtResult::AcceptH0
+        } else if llr >= upper {
+            SprtResult::AcceptH1
+        } else {
+            SprtResult::Continue
+        }
+    }
+}
+
+#[test]
+fn test_elo() {
+    assert_eq!(elo_from_score(0.5), 0.0);
+    assert!((elo_from_score(0.75) - 190.85).abs() < 0.01);
+    assert!((score_from_elo(elo_from_score(0.3)) - 0.3).abs() < 1e-6);
+
+    let stats = MatchStats {
+        wins: 30,
+        losses: 10,
+        draws: 0,
+    };
+    assert_eq!(stats.score(), 0.75);
+    let (low, high) = stats.elo_interval(1.96);
+    assert!(low < stats.elo() && stats.elo() < high);
+    assert!(low > 0.0);
+}
+
+#[test]
+fn test_sprt() {
+    let sprt = Sprt::new(0.0, 50.0, 0.05, 0.05);
+    let mut stats = MatchStats::default();
+    assert_eq!(sprt.judge(&stats), SprtResult::Continue);
+    for i in 0..400 {
+        stats.add(Some(i % 3 != 0));
+    }
+    assert_eq!(sprt.judge(&stats), SprtResult::AcceptH1);
+    let stats = MatchStats {
+        wins: 150,
+        losses: 250,
+        draws: 0,
+    };
+    assert_eq!(sprt.judge(&stats), SprtResult::AcceptH0);
+
+    // 全勝でも対局数が増えれば判定できる
+    let mut stats = MatchStats::default();
+    for _ in 0..10 {
+        stats.add(Some(true));
+    }
+    assert_eq!(sprt.judge(&stats), SprtResult::Continue);
+    for _ in 0..20 {
+        stats.add(Some(true));
+    }
+    assert_eq!(sprt.judge(&stats), SprtResult::AcceptH1);
+    let stats = MatchStats {
+        wins: 0,
+        losses: 30,
+        draws: 0,
+    };
+    assert_eq!(sprt.judge(&stats), SprtResult::AcceptH0);
+}
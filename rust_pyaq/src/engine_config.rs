@@ -0,0 +1,246 @@
+//! 起動時に読み込むエンジンの設定ファイルです。
+//!
+//! 設定ファイルはTOMLで、以下のような形式です。省略した項目はデフォルト値またはコマンドライン引数の値を使います。
+//! コマンドライン引数は設定ファイルより優先します。
+//!
+//! ```toml
+//! model = "frozen_model.pb"  # 相対パスは設定ファイルのディレクトリからのパス
+//! threads = 1
+//! playouts = 0
+//! rollouts = 0  # 0より大きければネットワークの代わりにロールアウトで評価する
+//! rollout_policy = "heuristic"
+//!
+//! [time]
+//! main_time = 0.0
+//! byoyomi = 3.0
+//! byoyomi_periods = 1
+//! canadian_stones = 0
+//! lag = 0.2
+//! extension = 1.0
+//! reallocate = false
+//!
+//! [search]  # search_config::PARAMSのパラメータ
+//! c_puct = 1.5
+//! fpu = "reduction:0.2"
+//!
+//! [resign]
+//! threshold = 0.1
+//! min_move = 0
+//! consecutive = 1
+//!
+//! [rules]
+//! rules = "chinese"
+//! komi = 7.0
+//!
+//! [log]
+//! level = "info"
+//! file = "rsaq.log"
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use toml;
+use constants::*;
+use search_config::SearchConfig;
+use logger::LogSpec;
+use evaluator::RolloutPolicy;
+
+/// モデルファイルのデフォルトのパスです。
+pub const DEFAULT_MODEL: &str = "frozen_model.pb";
+
+/// 持ち時間の設定です。
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimeSection {
+    pub main_time: Option<f32>,
+    pub byoyomi: Option<f32>,
+    pub byoyomi_periods: Option<usize>,
+    pub canadian_stones: Option<usize>,
+    pub lag: Option<f32>,
+    pub extension: Option<f32>,
+    pub reallocate: Option<bool>,
+}
+
+/// 投了の設定です。
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResignSection {
+    pub threshold: Option<f32>,
+    pub min_move: Option<usize>,
+    pub consecutive: Option<usize>,
+}
+
+/// ルールの設定です。エンジンが対応しているルールとコミかどうかを検証するためのものです。
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RulesSection {
+    pub rules: Option<String>,
+    pub komi: Option<f32>,
+}
+
+/// ログの設定です。
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogSection {
+    /// off, error, warn, info, debug, traceのいずれかです。
+    /// "info,search=warn"のようにターゲットごとにも指定できます。(logger参照)
+    pub level: Option<String>,
+    /// ログを書き出すファイルです。省略した場合は標準エラー出力です。
+    pub file: Option<PathBuf>,
+}
+
+/// エンジンの設定です。
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EngineConfig {
+    pub model: Option<PathBuf>,
+    pub threads: Option<usize>,
+    pub playouts: Option<usize>,
+    pub rollouts: Option<usize>,
+    pub rollout_policy: Option<String>,
+    pub time: TimeSection,
+    pub search: BTreeMap<String, toml::Value>,
+    pub resign: ResignSection,
+    pub rules: RulesSection,
+    pub log: LogSection,
+}
+
+/// 非負でなければエラーを返します。
+fn check_non_negative(name: &str, value: Option<f32>) -> Result<(), String> {
+    match value {
+        Some(v) if !(v >= 0.0) => Err(format!("{}: must be non-negative", name)),
+        _ => Ok(()),
+    }
+}
+
+impl EngineConfig {
+    /// TOMLのテキストtextから読み込みます。
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    /// 設定ファイルpathを読み込んで検証します。モデルファイルの相対パスは設定ファイルのディレクトリからのパスに直します。
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        use std::fs::File;
+        use std::io::Read;
+
+        let path = path.as_ref();
+        let error = |e: String| format!("{}: {}", path.display(), e);
+        let mut text = String::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut text))
+            .map_err(|e| error(e.to_string()))?;
+        let mut config = Self::from_toml(&text).map_err(&error)?;
+        if let Some(ref mut model) = config.model {
+            if model.is_relative() {
+                if let Some(dir) = path.parent() {
+                    *model = dir.join(&model);
+                }
+            }
+        }
+        config.validate().map_err(&error)?;
+        Ok(config)
+    }
+
+    /// モデルファイルのパスを返します。
+    pub fn model_path(&self) -> PathBuf {
+        self.model.clone().unwrap_or(PathBuf::from(DEFAULT_MODEL))
+    }
+
+    /// 値の範囲やエンジンが対応していない設定を検証します。
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(ref model) = self.model {
+            if !model.exists() {
+                return Err(format!("model: {} not found", model.display()));
+            }
+        }
+        match self.threads {
+            Some(0) => return Err("threads: must be at least 1".to_string()),
+            Some(n) if n > 1 => {
+                return Err("threads: only single-threaded search is supported".to_string())
+            }
+            _ => {}
+        }
+        if let Some(ref policy) = self.rollout_policy {
+            policy
+                .parse::<RolloutPolicy>()
+                .map_err(|e| format!("rollout_policy: {}", e))?;
+        }
+        check_non_negative("time.main_time", self.time.main_time)?;
+        check_non_negative("time.byoyomi", self.time.byoyomi)?;
+        check_non_negative("time.lag", self.time.lag)?;
+        check_non_negative("time.extension", self.time.extension)?;
+        if let Some(t) = self.resign.threshold {
+            if !(t >= 0.0 && t <= 1.0) {
+                return Err("resign.threshold: must be between 0 and 1".to_string());
+            }
+        }
+        if let Some(ref rules) = self.rules.rules {
+            let rules = rules.to_lowercase();
+            if rules != "chinese" && rules != "tromp-taylor" {
+                return Err(format!(
+                    "rules.rules: {} is not supported (chinese or tromp-taylor)",
+                    rules
+                ));
+            }
+        }
+        if let Some(komi) = self.rules.komi {
+            if komi != KOMI {
+                return Err(format!("rules.komi: only {} is supported", KOMI));
+            }
+        }
+        if let Some(ref level) = self.log.level {
+            level
+                .parse::<LogSpec>()
+                .map_err(|e| format!("log.level: {}", e))?;
+        }
+        self.search_config(SearchConfig::default())?;
+        Ok(())
+    }
+
+    /// baseを[search]の値で上書きした探索パラメータを返します。
+    pub fn search_config(&self, base: SearchConfig) -> Result<SearchConfig, String> {
+        let mut config = base;
+        for (name, value) in &self.search {
+            let value = match *value {
+                toml::Value::String(ref s) => s.clone(),
+                ref v => v.to_string(),
+            };
+            config
+                .set(name, &value)
+                .map_err(|e| format!("search.{}: {}", name, e))?;
+        }
+        Ok(config)
+    }
+}
+
+#[test]
+fn test_engine_config() {
+    let config = EngineConfig::from_toml(
+        r#"
+        playouts = 800
+        [time]
+        byoyomi = 5.0
+        [search]
+        c_puct = 2.0
+        fpu = "loss"
+        lcb = true
+        "#,
+    ).unwrap();
+    assert_eq!(config.playouts, Some(800));
+    assert_eq!(config.time.byoyomi, Some(5.0));
+    assert_eq!(config.time.main_time, None);
+    assert_eq!(config.model_path(), PathBuf::from(DEFAULT_MODEL));
+    let search = config.search_config(SearchConfig::default()).unwrap();
+    assert_eq!(search.c_puct, 2.0);
+    assert!(search.lcb);
+    assert!(config.validate().is_ok());
+
+    assert!(EngineConfig::from_toml("playout = 800").is_err());
+    let config = EngineConfig::from_toml("[search]\nc_puc = 1.0").unwrap();
+    assert!(config.validate().unwrap_err().starts_with("search.c_puc"));
+    let config = EngineConfig::from_toml("[log]\nlevel = \"info,board=verbose\"").unwrap();
+    assert!(config.validate().unwrap_err().starts_with("log.level"));
+    let config = EngineConfig::from_toml("[rules]\nkomi = 6.5").unwrap();
+    assert!(config.validate().unwrap_err().starts_with("rules.komi"));
+}
@@ -0,0 +1,237 @@
+use std::io::{self, Read};
+use getopts;
+use serde_json;
+use constants::*;
+use intersection::{Color, Intersection};
+use coord_convert::*;
+use board::Board;
+use numpy as np;
+use search::{Evaluate, Tree};
+use gtp::read_file;
+use convert::{main_line, parse_sgf};
+use {add_engine_opts, parse_args, parse_opt, EngineSettings};
+
+/// 読み筋の最大の長さです。
+const MAX_PV_LEN: usize = 10;
+
+/// ポリシーの上位の着手です。
+#[derive(Serialize)]
+struct PolicyMove {
+    #[serde(rename = "move")]
+    mov: String,
+    prior: f32,
+}
+
+/// 探索した候補手です。
+#[derive(Serialize)]
+struct SearchMove {
+    #[serde(rename = "move")]
+    mov: String,
+    visits: usize,
+    win_rate: f32,
+    prior: f32,
+    pv: Vec<String>,
+}
+
+/// 探索の結果です。
+#[derive(Serialize)]
+struct SearchOutput {
+    best: String,
+    win_rate: f32,
+    moves: Vec<SearchMove>,
+}
+
+/// evalの出力です。勝率はすべて手番から見た値です。
+#[derive(Serialize)]
+struct EvalOutput {
+    /// 上の行から順に、Xが黒石、Oが白石、.が空点です。
+    board: Vec<String>,
+    turn: &'static str,
+    move_number: usize,
+    last_move: Option<String>,
+    win_rate: f32,
+    /// A1, B1, ..., J1, A2, ..., J9, passの順のポリシーです。
+    policy: Vec<f32>,
+    top_moves: Vec<PolicyMove>,
+    search: Option<SearchOutput>,
+}
+
+/// GTPの座標の文字表現を拡張碁盤の線形座標に変換します。str2evと違い、不正な座標はエラーを返します。
+fn parse_move(s: &str) -> Result<usize, String> {
+    let upper = s.to_uppercase();
+    if upper == "PASS" {
+        return Ok(PASS);
+    }
+    let mut chars = upper.chars();
+    let x = chars
+        .next()
+        .and_then(|c| X_LABELS[1..BSIZE + 1].iter().position(|&e| e == c));
+    let y = chars.as_str().parse::<usize>().ok();
+    match (x, y) {
+        (Some(x), Some(y)) if y >= 1 && y <= BSIZE => Ok(xy2ev(x as u8 + 1, y as u8)),
+        _ => Err(format!("invalid move {}", s)),
+    }
+}
+
+/// 初期局面から着手の列movesを打った局面を返します。
+fn play_moves(moves: &[usize]) -> Result<Board, String> {
+    let mut b = Board::new();
+    for (i, &mov) in moves.iter().enumerate() {
+        if b.play(mov, false).is_err() {
+            return Err(format!("illegal move {} at move {}", ev2str(mov), i + 1));
+        }
+    }
+    Ok(b)
+}
+
+/// ファイルnameの内容を返します。nameが-の時は標準入力から読み込みます。
+fn read_input(name: &str) -> Result<String, String> {
+    if name == "-" {
+        let mut text = String::new();
+        io::stdin()
+            .read_to_string(&mut text)
+            .map_err(|e| e.to_string())?;
+        Ok(text)
+    } else {
+        read_file(name).map_err(|e| format!("{}: {}", name, e))
+    }
+}
+
+/// オプションで指定された局面を返します。指定がなければ初期局面です。
+fn load_position(matches: &getopts::Matches) -> Result<Board, String> {
+    let given = ["moves", "sgf", "diagram"]
+        .iter()
+        .filter(|&&name| matches.opt_present(name))
+        .count();
+    if given > 1 {
+        return Err("specify only one of --moves, --sgf and --diagram".to_string());
+    }
+    if let Some(moves) = matches.opt_str("moves") {
+        let moves = moves
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|s| !s.is_empty())
+            .map(parse_move)
+            .collect::<Result<Vec<_>, _>>()?;
+        play_moves(&moves)
+    } else if let Some(name) = matches.opt_str("sgf") {
+        let sgf = read_input(&name)?;
+        let collection = parse_sgf(&sgf)?;
+        let moves = main_line(&collection)?;
+        let move_number = parse_opt(matches, "move_number", moves.len());
+        if move_number > moves.len() {
+            return Err(format!("the game has only {} moves", moves.len()));
+        }
+        play_moves(&moves[..move_number])
+    } else if let Some(name) = matches.opt_str("diagram") {
+        let turn = match &matches.opt_str("turn").unwrap_or("b".to_string()).to_lowercase()[..] {
+            "b" | "black" => Color::Black,
+            "w" | "white" => Color::White,
+            t => return Err(format!("--turn: unknown color {}", t)),
+        };
+        Board::from_diagram(&read_input(&name)?, turn)
+    } else {
+        Ok(Board::new())
+    }
+}
+
+/// 盤面を上の行から順に文字列にします。
+fn board_rows(b: &Board) -> Vec<String> {
+    (1..(BSIZE + 1) as u8)
+        .rev()
+        .map(|y| {
+            (1..(BSIZE + 1) as u8)
+                .map(|x| match b.get_state(xy2ev(x, y)) {
+                    Intersection::Stone(Color::Black) => 'X',
+                    Intersection::Stone(Color::White) => 'O',
+                    _ => '.',
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// 局面bをネットワークで評価し、searchがtrueなら探索もします。ポリシーの上位はtop手出力します。
+fn evaluate<T: Evaluate>(tree: &mut Tree<T>, b: &Board, top: usize, search: bool) -> EvalOutput {
+    let (policy, value) = tree.nn.evaluate(b);
+    let top_moves = np::argsort(&policy, true)
+        .into_iter()
+        .filter(|&rv| b.legal(rv2ev(rv)))
+        .take(top)
+        .map(|rv| PolicyMove {
+            mov: ev2str(rv2ev(rv)),
+            prior: policy[rv],
+        })
+        .collect();
+    let search = if search {
+        let (best, win_rate) = tree.search(b, 0.0, false, false);
+        let moves = tree.root_moves()
+            .into_iter()
+            .take(top)
+            .map(|stats| SearchMove {
+                mov: ev2str(stats.mov),
+                visits: stats.visit_cnt,
+                win_rate: stats.win_rate,
+                prior: stats.prob,
+                pv: tree.principal_variation(stats.mov, MAX_PV_LEN)
+                    .into_iter()
+                    .map(ev2str)
+                    .collect(),
+            })
+            .collect();
+        Some(SearchOutput {
+            best: ev2str(best),
+            win_rate: win_rate,
+            moves: moves,
+        })
+    } else {
+        None
+    };
+    let prev_move = b.get_prev_move();
+    EvalOutput {
+        board: board_rows(b),
+        turn: match b.get_turn() {
+            Color::Black => "B",
+            Color::White => "W",
+        },
+        move_number: b.get_move_cnt(),
+        last_move: if prev_move == VNULL {
+            None
+        } else {
+            Some(ev2str(prev_move))
+        },
+        win_rate: value[0] / 2.0 + 0.5,
+        policy: policy,
+        top_moves: top_moves,
+        search: search,
+    }
+}
+
+/// evalサブコマンドを実行します。
+pub fn eval_main(program: &str, args: &[String]) {
+    let mut opts = getopts::Options::new();
+    opts.optflag("h", "help", "Help")
+        .optopt("", "moves", "Moves from the empty board, e.g. \"E5 C4 pass\".", "MOVES")
+        .optopt("", "sgf", "SGF file of the position. - reads stdin.", "FILE")
+        .optopt("", "move_number", "Number of moves to replay in --sgf. default: all", "NUM")
+        .optopt("", "diagram", "Board diagram file in showboard format. - reads stdin.", "FILE")
+        .optopt("", "turn", "Color to play in --diagram: b or w. default: b", "COLOR")
+        .optopt("", "top", "Number of top moves to print. default: 10", "NUM");
+    add_engine_opts(&mut opts);
+    let brief = format!(
+        "Usage: {} eval [options]\n\n\
+         Prints the network output for the position as JSON. \
+         With --playouts the position is also searched.",
+        program
+    );
+    let matches = parse_args(&opts, args, &brief);
+    let settings = EngineSettings::new(&matches, false);
+    settings.check_model();
+    let b = load_position(&matches).unwrap_or_else(|e| {
+        eprintln!("eval: {}", e);
+        std::process::exit(1);
+    });
+    let mut tree = settings.make_tree();
+    let top = parse_opt(&matches, "top", 10);
+    let output = evaluate(&mut tree, &b, top, settings.max_playout > 0);
+    println!("{}", serde_json::to_string_pretty(&output).unwrap());
+}
@@ -0,0 +1,574 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use rand::{Rng, XorShiftRng};
+use constants::*;
+use intersection::Color;
+use coord_convert::rv2ev;
+use board::Board;
+use search::Evaluate;
+use symmetry::*;
+use utils::{entropy_rng, seeded_rng};
+
+/// 評価の際に局面に施す対称変換の選び方です。
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SymmetryMode {
+    /// 局面をそのまま評価します。
+    None,
+    /// 評価のたびにランダムに選んだ対称変換で評価します。
+    Random,
+    /// 8通りの対称変換で評価して平均します。
+    Average,
+}
+
+impl FromStr for SymmetryMode {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(SymmetryMode::None),
+            "random" => Ok(SymmetryMode::Random),
+            "average" => Ok(SymmetryMode::Average),
+            _ => Err("unknown symmetry mode"),
+        }
+    }
+}
+
+/// 局面を対称変換してから内側の評価器で評価し、方策を元の向きに戻して返す評価器です。
+/// 棋譜から学習したネットワークの向きによる偏りを減らします。
+pub struct SymmetricEvaluator<T: Evaluate> {
+    pub inner: T,
+    mode: SymmetryMode,
+    rng: XorShiftRng,
+}
+
+impl<T: Evaluate> SymmetricEvaluator<T> {
+    pub fn new(inner: T, mode: SymmetryMode) -> Self {
+        Self {
+            inner: inner,
+            mode: mode,
+            rng: entropy_rng(),
+        }
+    }
+
+    /// Randomの時に対称変換を選ぶ乱数生成器をシードseedで初期化します。
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = seeded_rng(seed);
+    }
+
+    pub fn get_mode(&self) -> SymmetryMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: SymmetryMode) {
+        self.mode = mode;
+    }
+
+    /// 対称変換symで移した局面を評価し、方策を元の向きに戻して返します。
+    fn evaluate_with(&mut self, board: &Board, sym: usize) -> (Vec<f32>, Vec<f32>) {
+        if sym == 0 {
+            return self.inner.evaluate(board);
+        }
+        let (prob, value) = self.inner.evaluate(&transform_board(board, sym));
+        (transform_policy(&prob, inverse(sym)), value)
+    }
+}
+
+impl<T: Evaluate> Evaluate for SymmetricEvaluator<T> {
+    fn evaluate(&mut self, board: &Board) -> (Vec<f32>, Vec<f32>) {
+        match self.mode {
+            SymmetryMode::None => self.inner.evaluate(board),
+            SymmetryMode::Random => {
+                let sym = self.rng.gen_range(0, SYMMETRY_CNT);
+                self.evaluate_with(board, sym)
+            }
+            SymmetryMode::Average => {
+                let (mut prob, mut value) = self.evaluate_with(board, 0);
+                for sym in 1..SYMMETRY_CNT {
+                    let (p, v) = self.evaluate_with(board, sym);
+                    for (e, x) in prob.iter_mut().zip(p) {
+                        *e += x;
+                    }
+                    for (e, x) in value.iter_mut().zip(v) {
+                        *e += x;
+                    }
+                }
+                for e in prob.iter_mut().chain(value.iter_mut()) {
+                    *e /= SYMMETRY_CNT as f32;
+                }
+                (prob, value)
+            }
+        }
+    }
+
+    fn info(&self) -> Option<String> {
+        self.inner.info()
+    }
+}
+
+const NIL: usize = usize::max_value();
+
+/// LRUキャッシュの要素です。prevとnextで最近使った順の双方向リストを作ります。
+struct CacheEntry {
+    key: u64,
+    prob: Vec<f32>,
+    value: Vec<f32>,
+    prev: usize,
+    next: usize,
+}
+
+/// 局面のキーposition_keyから評価結果を引く、容量capacityのLRUキャッシュを持つ評価器です。
+/// 合流や待ったで同じ局面を何度も評価するのを避けます。
+pub struct CachedEvaluator<T: Evaluate> {
+    pub inner: T,
+    capacity: usize,
+    index: HashMap<u64, usize>,
+    entries: Vec<CacheEntry>,
+    head: usize, // 最も最近使った要素
+    tail: usize, // 最も長く使っていない要素
+    lookup_cnt: usize,
+    hit_cnt: usize,
+}
+
+impl<T: Evaluate> CachedEvaluator<T> {
+    /// capacityが0の場合はキャッシュしません。
+    pub fn new(inner: T, capacity: usize) -> Self {
+        Self {
+            inner: inner,
+            capacity: capacity,
+            index: HashMap::with_capacity(capacity),
+            entries: Vec::with_capacity(capacity),
+            head: NIL,
+            tail: NIL,
+            lookup_cnt: 0,
+            hit_cnt: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// キャッシュを空にし、統計をリセットします。
+    pub fn clear(&mut self) {
+        self.index.clear();
+        self.entries.clear();
+        self.head = NIL;
+        self.tail = NIL;
+        self.lookup_cnt = 0;
+        self.hit_cnt = 0;
+    }
+
+    /// これまでの検索回数とヒット回数を返します。
+    pub fn get_stats(&self) -> (usize, usize) {
+        (self.lookup_cnt, self.hit_cnt)
+    }
+
+    /// ヒット率を返します。検索がなければ0です。
+    pub fn hit_rate(&self) -> f32 {
+        self.hit_cnt as f32 / self.lookup_cnt.max(1) as f32
+    }
+
+    /// 要素iを双方向リストから外します。
+    fn unlink(&mut self, i: usize) {
+        let (prev, next) = (self.entries[i].prev, self.entries[i].next);
+        if prev == NIL {
+            self.head = next;
+        } else {
+            self.entries[prev].next = next;
+        }
+        if next == NIL {
+            self.tail = prev;
+        } else {
+            self.entries[next].prev = prev;
+        }
+    }
+
+    /// 要素iを双方向リストの先頭に入れます。
+    fn push_front(&mut self, i: usize) {
+        self.entries[i].prev = NIL;
+        self.entries[i].next = self.head;
+        if self.head != NIL {
+            self.entries[self.head].prev = i;
+        }
+        self.head = i;
+        if self.tail == NIL {
+            self.tail = i;
+        }
+    }
+
+    /// 評価結果を追加します。容量に達していたら最も長く使っていない要素と入れ替えます。
+    fn insert(&mut self, key: u64, prob: &[f32], value: &[f32]) {
+        let entry = CacheEntry {
+            key: key,
+            prob: prob.to_vec(),
+            value: value.to_vec(),
+            prev: NIL,
+            next: NIL,
+        };
+        let i = if self.entries.len() < self.capacity {
+            self.entries.push(entry);
+            self.entries.len() - 1
+        } else {
+            let i = self.tail;
+            self.unlink(i);
+            self.index.remove(&self.entries[i].key);
+            self.entries[i] = entry;
+            i
+        };
+        self.index.insert(key, i);
+        self.push_front(i);
+    }
+}
+
+impl<T: Evaluate> Evaluate for CachedEvaluator<T> {
+    fn evaluate(&mut self, board: &Board) -> (Vec<f32>, Vec<f32>) {
+        if self.capacity == 0 {
+            return self.inner.evaluate(board);
+        }
+        let key = board.position_key();
+        self.lookup_cnt += 1;
+        if let Some(&i) = self.index.get(&key) {
+            self.hit_cnt += 1;
+            self.unlink(i);
+            self.push_front(i);
+            let entry = &self.entries[i];
+            return (entry.prob.clone(), entry.value.clone());
+        }
+        let (prob, value) = self.inner.evaluate(board);
+        self.insert(key, &prob, &value);
+        (prob, value)
+    }
+
+    fn info(&self) -> Option<String> {
+        if self.capacity == 0 {
+            return self.inner.info();
+        }
+        let info = format!(
+            "nn cache: hits={}/{} ({:.1}%) size={}/{}",
+            self.hit_cnt,
+            self.lookup_cnt,
+            self.hit_rate() * 100.0,
+            self.len(),
+            self.capacity
+        );
+        Some(match self.inner.info() {
+            Some(inner) => format!("{} {}", info, inner),
+            None => info,
+        })
+    }
+}
+
+/// 一様なポリシーと0のバリューを返す評価器です。
+/// モデルファイルがない時のベンチマークなどに使います。
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UniformEvaluator;
+
+impl Evaluate for UniformEvaluator {
+    fn evaluate(&mut self, _: &Board) -> (Vec<f32>, Vec<f32>) {
+        (vec![1.0 / (BVCNT + 1) as f32; BVCNT + 1], vec![0.0])
+    }
+}
+
+/// RolloutEvaluatorの方策の決め方です。
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RolloutPolicy {
+    /// 合法手(眼を埋める手を除く)とパスに一様です。
+    Uniform,
+    /// 石を取る手、アタリから逃げる手、アタリにする手を重くし、自らアタリになる手とパスを軽くします。
+    Heuristic,
+}
+
+impl FromStr for RolloutPolicy {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uniform" => Ok(RolloutPolicy::Uniform),
+            "heuristic" => Ok(RolloutPolicy::Heuristic),
+            _ => Err("unknown rollout policy"),
+        }
+    }
+}
+
+/// RolloutPolicy::Heuristicの各着手の重みです。
+const CAPTURE_WEIGHT: f32 = 8.0;
+const SAVE_WEIGHT: f32 = 6.0;
+const ATARI_WEIGHT: f32 = 2.0;
+const SELF_ATARI_RATIO: f32 = 0.2;
+const PASS_WEIGHT: f32 = 0.1;
+
+/// 候補手の方策をRolloutPolicyの重みで、バリューをランダムロールアウトの勝敗の平均で返す評価器です。
+/// ニューラルネットワークなしで探索するために使います。
+pub struct RolloutEvaluator {
+    rollout_cnt: usize,
+    policy: RolloutPolicy,
+    rng: XorShiftRng,
+    board: Board, // ロールアウトのための作業用の碁盤
+}
+
+impl RolloutEvaluator {
+    /// 1回の評価でrollout_cnt回ロールアウトします。方策はHeuristicです。
+    pub fn new(rollout_cnt: usize) -> Self {
+        Self {
+            rollout_cnt: rollout_cnt.max(1),
+            policy: RolloutPolicy::Heuristic,
+            rng: entropy_rng(),
+            board: Board::new(),
+        }
+    }
+
+    /// ロールアウトの乱数生成器をシードseedで初期化します。
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = seeded_rng(seed);
+    }
+
+    pub fn get_rollout_cnt(&self) -> usize {
+        self.rollout_cnt
+    }
+
+    pub fn get_policy(&self) -> RolloutPolicy {
+        self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: RolloutPolicy) {
+        self.policy = policy;
+    }
+
+    /// 局面boardの候補手の重みを正規化した方策を返します。
+    fn policy(&self, board: &Board) -> Vec<f32> {
+        let mut prob = vec![0.0; BVCNT + 1];
+        for &rv in &board.candidates().list {
+            prob[rv] = match self.policy {
+                RolloutPolicy::Uniform => 1.0,
+                RolloutPolicy::Heuristic if rv == BVCNT => PASS_WEIGHT,
+                RolloutPolicy::Heuristic => {
+                    let t = board.tactics(rv2ev(rv));
+                    let mut weight = 1.0;
+                    if t.capture_cnt > 0 {
+                        weight += CAPTURE_WEIGHT;
+                    }
+                    if t.saves_atari {
+                        weight += SAVE_WEIGHT;
+                    }
+                    if t.atari_cnt > 0 {
+                        weight += ATARI_WEIGHT;
+                    }
+                    if t.self_atari {
+                        weight *= SELF_ATARI_RATIO;
+                    }
+                    weight
+                }
+            };
+        }
+        let sum: f32 = prob.iter().sum();
+        for p in prob.iter_mut() {
+            *p /= sum;
+        }
+        prob
+    }
+}
+
+impl Evaluate for RolloutEvaluator {
+    fn evaluate(&mut self, board: &Board) -> (Vec<f32>, Vec<f32>) {
+        let prob = self.policy(board);
+        let mut total = 0.0;
+        for _ in 0..self.rollout_cnt {
+            board.copy_to(&mut self.board);
+            self.board.rollout(false, &mut self.rng);
+            let score = self.board.area_score();
+            total += if score > 0.0 {
+                1.0
+            } else if score < 0.0 {
+                -1.0
+            } else {
+                0.0
+            };
+        }
+        let value = total / self.rollout_cnt as f32; // 黒から見たバリュー
+        let value = if board.get_turn() == Color::Black {
+            value
+        } else {
+            -value
+        };
+        (prob, vec![value])
+    }
+}
+
+/// 局面ごとに設定した評価結果を返す評価器です。探索のテストに使います。
+/// 設定していない局面には一様な方策と0のバリューを返します。
+#[derive(Default)]
+pub struct ScriptedEvaluator {
+    outputs: HashMap<u64, (Vec<f32>, Vec<f32>)>,
+    eval_cnt: usize,
+}
+
+impl ScriptedEvaluator {
+    pub fn new() -> Self {
+        Self {
+            outputs: HashMap::new(),
+            eval_cnt: 0,
+        }
+    }
+
+    /// 局面boardの評価結果を方策prob、手番から見たバリューvalueにします。
+    pub fn set(&mut self, board: &Board, prob: Vec<f32>, value: f32) {
+        assert_eq!(prob.len(), BVCNT + 1);
+        self.outputs.insert(board.position_key(), (prob, vec![value]));
+    }
+
+    /// 局面boardの方策はそのままで、バリューだけをvalueにします。
+    pub fn set_value(&mut self, board: &Board, value: f32) {
+        let prob = UniformEvaluator.evaluate(board).0;
+        self.set(board, prob, value);
+    }
+
+    /// これまでに評価した回数を返します。
+    pub fn get_eval_cnt(&self) -> usize {
+        self.eval_cnt
+    }
+}
+
+impl Evaluate for ScriptedEvaluator {
+    fn evaluate(&mut self, board: &Board) -> (Vec<f32>, Vec<f32>) {
+        self.eval_cnt += 1;
+        match self.outputs.get(&board.position_key()) {
+            Some(output) => output.clone(),
+            None => UniformEvaluator.evaluate(board),
+        }
+    }
+}
+
+#[test]
+fn test_symmetric_evaluator() {
+    use numpy as np;
+
+    // 左上の隅に高い確率を返す、向きに依存する評価器
+    struct Corner;
+
+    impl Evaluate for Corner {
+        fn evaluate(&mut self, _: &Board) -> (Vec<f32>, Vec<f32>) {
+            let mut prob = vec![0.0; BVCNT + 1];
+            prob[0] = 1.0;
+            (prob, vec![0.5])
+        }
+    }
+
+    let b = Board::new();
+    let corners = [0, BSIZE - 1, BVCNT - BSIZE, BVCNT - 1];
+    let (prob, value) = SymmetricEvaluator::new(Corner, SymmetryMode::None).evaluate(&b);
+    assert_eq!((prob[0], value[0]), (1.0, 0.5));
+
+    let (prob, value) = SymmetricEvaluator::new(Corner, SymmetryMode::Average).evaluate(&b);
+    for &rv in &corners {
+        assert_eq!(prob[rv], 0.25);
+    }
+    assert_eq!(value[0], 0.5);
+
+    let mut evaluator = SymmetricEvaluator::new(Corner, SymmetryMode::Random);
+    evaluator.set_seed(1);
+    let (prob, _) = evaluator.evaluate(&b);
+    assert!(corners.contains(&np::argmax(prob.iter())));
+}
+
+#[test]
+fn test_symmetric_evaluator_diagram() {
+    // 石の取り合いで重みが変わる、向きによらない方策で履歴のない非対称な局面を評価する
+    let diagram = "
+        . . . . . . . . .
+        . . . . . . . . .
+        . . X O . . . . .
+        . X O . O . . . .
+        . . X O . . . . .
+        . . . X . . . . .
+        . . . . . . O . .
+        . . . . . . . . .
+        . . . . . . . . .
+    ";
+    let b = Board::from_diagram(diagram, Color::Black).unwrap();
+    let expected = RolloutEvaluator::new(1).policy(&b);
+    let mut evaluator = SymmetricEvaluator::new(RolloutEvaluator::new(1), SymmetryMode::None);
+    for sym in 0..SYMMETRY_CNT {
+        let (prob, _) = evaluator.evaluate_with(&b, sym);
+        for (&p, &e) in prob.iter().zip(expected.iter()) {
+            assert!((p - e).abs() < 1e-6);
+        }
+    }
+    evaluator.set_mode(SymmetryMode::Average);
+    let (prob, _) = evaluator.evaluate(&b);
+    for (&p, &e) in prob.iter().zip(expected.iter()) {
+        assert!((p - e).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn test_cached_evaluator() {
+    use coord_convert::*;
+
+    // 評価した回数を数える評価器
+    struct Counter(usize);
+
+    impl Evaluate for Counter {
+        fn evaluate(&mut self, _: &Board) -> (Vec<f32>, Vec<f32>) {
+            self.0 += 1;
+            (vec![1.0 / (BVCNT + 1) as f32; BVCNT + 1], vec![self.0 as f32])
+        }
+    }
+
+    let mut evaluator = CachedEvaluator::new(Counter(0), 2);
+    let b0 = Board::new();
+    let mut b1 = Board::new();
+    let _ = b1.play(xy2ev(3, 3), false);
+    let mut b2 = Board::new();
+    let _ = b2.play(xy2ev(4, 4), false);
+
+    assert_eq!(evaluator.evaluate(&b0).1, vec![1.0]);
+    assert_eq!(evaluator.evaluate(&b1).1, vec![2.0]);
+    assert_eq!(evaluator.evaluate(&b0).1, vec![1.0]); // ヒットしてb0が最近使った要素になる
+    assert_eq!(evaluator.evaluate(&b2).1, vec![3.0]); // b1を追い出す
+    assert_eq!(evaluator.evaluate(&b0).1, vec![1.0]);
+    assert_eq!(evaluator.evaluate(&b1).1, vec![4.0]);
+    assert_eq!(evaluator.get_stats(), (6, 2));
+    assert_eq!(evaluator.len(), 2);
+    assert!(evaluator.info().unwrap().starts_with("nn cache: hits=2/6"));
+}
+
+#[test]
+fn test_stub_evaluators() {
+    use coord_convert::*;
+    use numpy as np;
+
+    let mut b = Board::new();
+    let (prob, value) = UniformEvaluator.evaluate(&b);
+    assert_eq!((prob.len(), value), (BVCNT + 1, vec![0.0]));
+
+    // 白の石がない局面からのロールアウトは黒の大勝になりやすい
+    for &v in &[xy2ev(3, 3), PASS, xy2ev(7, 7), PASS, xy2ev(3, 7), PASS, xy2ev(7, 3), PASS] {
+        let _ = b.play(v, false);
+    }
+    let mut evaluator = RolloutEvaluator::new(32);
+    evaluator.set_policy(RolloutPolicy::Uniform);
+    evaluator.set_seed(1);
+    let (prob, value) = evaluator.evaluate(&b);
+    assert_eq!(prob[ev2rv(xy2ev(3, 3))], 0.0);
+    assert_eq!(prob[ev2rv(xy2ev(5, 5))], prob[BVCNT]);
+    assert!((prob.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+    assert!(value[0] > 0.0);
+    evaluator.set_seed(1);
+    assert_eq!(evaluator.evaluate(&b).1, value);
+
+    // 黒C3をアタリにした局面では、白は取る手を重く、パスを軽くする
+    for &v in &[PASS, xy2ev(2, 3), PASS, xy2ev(3, 4), PASS, xy2ev(3, 2), PASS] {
+        let _ = b.play(v, false);
+    }
+    evaluator.set_policy(RolloutPolicy::Heuristic);
+    let (prob, _) = evaluator.evaluate(&b);
+    assert_eq!(np::argmax(prob.iter()), ev2rv(xy2ev(4, 3)));
+    assert!(prob[BVCNT] < prob[ev2rv(xy2ev(5, 5))]);
+    assert!((prob.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+
+    let mut evaluator = ScriptedEvaluator::new();
+    evaluator.set_value(&b, 0.5);
+    assert_eq!(evaluator.evaluate(&b).1, vec![0.5]);
+    assert_eq!(evaluator.evaluate(&Board::new()).1, vec![0.0]);
+    assert_eq!(evaluator.get_eval_cnt(), 2);
+}
@@ -1,15 +1,17 @@
 use std::io;
+use std::str::FromStr;
 use sgf::SgfCollection;
 use numpy as np;
 use constants::*;
 use intersection::*;
 use coord_convert::*;
 use board::*;
-use search::Tree;
-use neural_network::NeuralNetwork;
+use search::{Evaluate, Tree};
+use resign::ResignPolicy;
+use time_control::Overtime;
 
 fn response_list_commands() {
-    const CMD_LIST: [&str; 16] = [
+    const CMD_LIST: [&str; 18] = [
         "protocol_version",
         "name",
         "version",
@@ -18,6 +20,7 @@ fn response_list_commands() {
         "komi",
         "time_settings",
         "time_left",
+        "kgs-time_settings",
         "clear_board",
         "genmove",
         "play",
@@ -25,6 +28,7 @@ fn response_list_commands() {
         "gogui-play_sequence",
         "showboard",
         "loadsgf",
+        "set_param",
         "quit",
     ];
     print!("=");
@@ -52,7 +56,12 @@ fn parse(line: &str) -> (Option<&str>, Vec<&str>) {
     (command, args.collect())
 }
 
-fn move2xy(mov: &str) -> (u8, u8) {
+/// i番目の引数をT型として返します。
+fn arg<T: FromStr>(args: &[&str], i: usize) -> Option<T> {
+    args.get(i).and_then(|s| s.parse().ok())
+}
+
+pub fn move2xy(mov: &str) -> (u8, u8) {
     const OFFSET: u8 = 'a' as u8 - 1;
     let mut chars = mov.chars();
     let first = chars.next().unwrap();
@@ -60,7 +69,7 @@ fn move2xy(mov: &str) -> (u8, u8) {
     (first as u8 - OFFSET, second as u8 - OFFSET)
 }
 
-fn read_file(name: &str) -> io::Result<String> {
+pub fn read_file(name: &str) -> io::Result<String> {
     use std::fs::File;
     use std::io::Read;
 
@@ -71,22 +80,22 @@ fn read_file(name: &str) -> io::Result<String> {
 }
 
 /// GTPコマンドを待ち受け、実行するワーカーです。
-pub struct GtpClient {
+pub struct GtpClient<T: Evaluate> {
     b: Board,
-    tree: Tree<NeuralNetwork>,
+    tree: Tree<T>,
     quick: bool,
     clean: bool,
+    resign: ResignPolicy,
 }
 
-impl GtpClient {
-    pub fn new(main_time: f32, byoyomi: f32, quick: bool, clean: bool) -> Self {
-        let mut tree = Tree::new(NeuralNetwork::new("frozen_model.pb"));
-        tree.set_time(main_time, byoyomi);
+impl<T: Evaluate> GtpClient<T> {
+    pub fn new(tree: Tree<T>, quick: bool, clean: bool, resign: ResignPolicy) -> Self {
         GtpClient {
             b: Board::new(),
             tree: tree,
             quick: quick,
             clean: clean,
+            resign: resign,
         }
     }
 
@@ -105,6 +114,7 @@ impl GtpClient {
         if line.is_empty() {
             return true;
         }
+        debug!(target: "gtp", "{}", line);
         let (command, args) = parse(line);
         match command.unwrap() {
             "protocol_version" => {
@@ -144,22 +154,64 @@ impl GtpClient {
                 }
             }
             "time_settings" => {
-                self.tree
-                    .set_time(args[0].parse().unwrap(), args[1].parse().unwrap());
-                send("");
+                if let (Some(main_time), Some(byoyomi)) = (arg(&args, 0), arg(&args, 1)) {
+                    let stones = arg(&args, 2).unwrap_or(0);
+                    self.tree
+                        .time_manager_mut()
+                        .set_gtp_time_settings(main_time, byoyomi, stones);
+                    send("");
+                } else {
+                    println!("?syntax error\n");
+                }
+            }
+            "kgs-time_settings" => {
+                let overtime = match args.get(0) {
+                    Some(&"absolute") => Some(Overtime::Absolute),
+                    Some(&"byoyomi") => match (arg(&args, 2), arg(&args, 3)) {
+                        (Some(time), Some(periods)) => Some(Overtime::Byoyomi {
+                            time: time,
+                            periods: periods,
+                        }),
+                        _ => None,
+                    },
+                    Some(&"canadian") => match (arg(&args, 2), arg(&args, 3)) {
+                        (Some(time), Some(stones)) => Some(Overtime::Canadian {
+                            time: time,
+                            stones: stones,
+                        }),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                if args.get(0) == Some(&"none") {
+                    // 時間無制限の場合は設定済みの思考時間のまま
+                    send("");
+                } else if let (Some(main_time), Some(overtime)) = (arg(&args, 1), overtime) {
+                    self.tree
+                        .time_manager_mut()
+                        .set_time_settings(main_time, overtime);
+                    send("");
+                } else {
+                    println!("?syntax error\n");
+                }
             }
             "time_left" => {
-                self.tree.set_left_time(args[1].parse().unwrap());
-                send("");
+                if let (Some(time), Some(stones)) = (arg(&args, 1), arg(&args, 2)) {
+                    self.tree.time_manager_mut().set_time_left(time, stones);
+                    send("");
+                } else {
+                    println!("?syntax error\n");
+                }
             }
             "clear_board" => {
                 self.b.clear();
                 self.tree.clear();
+                self.resign.clear();
                 send("");
             }
             "genmove" => {
                 let (mov, win_rate) = self.best_move();
-                if win_rate < 0.1 {
+                if self.resign.should_resign(self.b.get_move_cnt(), win_rate) {
                     send("resign");
                 } else {
                     let _ = self.b.play(mov, true);
@@ -208,6 +260,16 @@ impl GtpClient {
                     println!("?missing filename\n");
                 }
             }
+            "set_param" => {
+                if let (Some(name), Some(value)) = (args.get(0), args.get(1)) {
+                    match self.tree.config_mut().set(name, value) {
+                        Ok(_) => send(""),
+                        Err(e) => println!("?{}\n", e),
+                    }
+                } else {
+                    println!("?missing parameter\n");
+                }
+            }
             "quit" => {
                 send("");
                 return false;
@@ -231,6 +293,7 @@ impl GtpClient {
     fn load_collection(&mut self, collection: &SgfCollection, mn: usize) -> Color {
         self.tree.clear();
         self.b.clear();
+        self.resign.clear();
         // TODO - play_sequenceを使う。generatorが良さそうだけどまだnightly
         let mut node = &collection[0];
         let mut n = 0;
@@ -258,7 +321,6 @@ impl GtpClient {
 
     /// 現局面の探索最善手と勝率を返します。手番はself.b.turnです。
     pub fn best_move(&mut self) -> (usize, f32) {
-        use rust_pyaq::search::Evaluate;
         if self.quick {
             (
                 rv2ev(np::argmax(self.tree.nn.evaluate(&self.b).0.iter())),
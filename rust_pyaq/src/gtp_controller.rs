@@ -0,0 +1,151 @@
+//! 外部のGTPエンジンのプロセスを起動し、コマンドを送って応答を受け取るコントローラーです。
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use constants::*;
+use coord_convert::*;
+use intersection::Color;
+
+/// GTPの応答textを解釈します。成功なら応答の本文、失敗ならエラーメッセージを返します。
+pub fn parse_response(text: &str) -> Result<String, String> {
+    let text = text.trim();
+    let (status, body) = text.split_at(text.chars().next().map_or(0, |c| c.len_utf8()));
+    // idがあれば取り除く
+    let body = body.trim_left_matches(|c: char| c.is_digit(10)).trim();
+    match status {
+        "=" => Ok(body.to_string()),
+        "?" => Err(body.to_string()),
+        _ => Err(format!("invalid response \"{}\"", text)),
+    }
+}
+
+/// GTPの座標vertexを拡張碁盤の線形座標に変換します。不正な座標の場合はNoneを返します。
+pub fn parse_vertex(vertex: &str) -> Option<usize> {
+    let vertex = vertex.to_uppercase();
+    if vertex == "PASS" {
+        return Some(PASS);
+    }
+    let mut chars = vertex.chars();
+    let x = chars
+        .next()
+        .and_then(|c| X_LABELS.iter().position(|&e| e == c))?;
+    let y = chars.collect::<String>().parse::<usize>().ok()?;
+    if x < 1 || x > BSIZE || y < 1 || y > BSIZE {
+        return None;
+    }
+    Some(xy2ev(x as u8, y as u8))
+}
+
+fn color2gtp(color: Color) -> &'static str {
+    match color {
+        Color::Black => "b",
+        Color::White => "w",
+    }
+}
+
+/// 外部のGTPエンジンです。破棄する時にquitを送って終了を待ちます。
+pub struct GtpController {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl GtpController {
+    /// 空白区切りのコマンドラインcommandでエンジンを起動します。
+    pub fn spawn(command: &str) -> io::Result<Self> {
+        let mut words = command.split_whitespace();
+        let program = words
+            .next()
+            .ok_or(io::Error::new(io::ErrorKind::InvalidInput, "empty command"))?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Ok(Self {
+            child: child,
+            stdin: stdin,
+            stdout: stdout,
+        })
+    }
+
+    /// コマンドcommandを送り、応答を返します。
+    pub fn send(&mut self, command: &str) -> Result<String, String> {
+        writeln!(self.stdin, "{}", command).map_err(|e| e.to_string())?;
+        self.stdin.flush().map_err(|e| e.to_string())?;
+        let mut response = String::new();
+        loop {
+            let mut line = String::new();
+            if self.stdout.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+                return Err(format!("{}: engine exited", command));
+            }
+            if line.trim().is_empty() {
+                if response.is_empty() {
+                    continue; // 応答の前の空行は読み飛ばす
+                }
+                break;
+            }
+            response.push_str(&line);
+        }
+        parse_response(&response).map_err(|e| format!("{}: {}", command, e))
+    }
+
+    /// エンジンの名前とバージョンを返します。
+    pub fn name(&mut self) -> Result<String, String> {
+        let name = self.send("name")?;
+        let version = self.send("version").unwrap_or(String::new());
+        Ok(format!("{} {}", name, version).trim().to_string())
+    }
+
+    /// 碁盤のサイズとコミを設定し、碁盤を空にします。
+    pub fn clear_board(&mut self) -> Result<(), String> {
+        self.send(&format!("boardsize {}", BSIZE))?;
+        self.send(&format!("komi {}", KOMI))?;
+        self.send("clear_board")?;
+        Ok(())
+    }
+
+    /// colorの着手movを伝えます。
+    pub fn play(&mut self, color: Color, mov: usize) -> Result<(), String> {
+        self.send(&format!("play {} {}", color2gtp(color), ev2str(mov)))?;
+        Ok(())
+    }
+
+    /// colorの着手を生成させます。投了の場合はNoneを返します。
+    pub fn genmove(&mut self, color: Color) -> Result<Option<usize>, String> {
+        let response = self.send(&format!("genmove {}", color2gtp(color)))?;
+        if response.to_lowercase() == "resign" {
+            return Ok(None);
+        }
+        parse_vertex(&response)
+            .map(Some)
+            .ok_or(format!("genmove: invalid vertex \"{}\"", response))
+    }
+}
+
+impl Drop for GtpController {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn test_parse_response() {
+    assert_eq!(parse_response("= D4\n\n"), Ok("D4".to_string()));
+    assert_eq!(parse_response("=12 pass"), Ok("pass".to_string()));
+    assert_eq!(parse_response("=\n"), Ok("".to_string()));
+    assert_eq!(
+        parse_response("? unknown command"),
+        Err("unknown command".to_string())
+    );
+    assert!(parse_response("hello").is_err());
+
+    assert_eq!(parse_vertex("pass"), Some(PASS));
+    assert_eq!(parse_vertex("d4"), Some(xy2ev(4, 4)));
+    assert_eq!(parse_vertex("J9"), Some(xy2ev(9, 9)));
+    assert_eq!(parse_vertex("I5"), None);
+    assert_eq!(parse_vertex("A10"), None);
+}
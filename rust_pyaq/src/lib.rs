@@ -1,11 +1,22 @@
 #![feature(box_syntax)]
 
+#[cfg(not(target_arch = "wasm32"))]
+extern crate flate2;
 extern crate itertools;
 #[cfg(feature = "ponder")]
 #[warn(unused_imports)]
 #[macro_use]
 extern crate lazy_static;
+#[macro_use]
+extern crate log;
 extern crate rand;
+#[cfg(not(target_arch = "wasm32"))]
+extern crate serde;
+#[cfg(not(target_arch = "wasm32"))]
+#[macro_use]
+extern crate serde_derive;
+#[cfg(not(target_arch = "wasm32"))]
+extern crate toml;
 
 pub mod utils;
 pub mod numpy;
@@ -14,4 +25,19 @@ pub mod intersection;
 pub mod coord_convert;
 pub mod stone_group;
 pub mod board;
+pub mod symmetry;
+pub mod search_config;
+pub mod time_control;
 pub mod search;
+pub mod evaluator;
+pub mod resign;
+pub mod elo;
+pub mod sgf_writer;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod training_data;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod gtp_controller;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod engine_config;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod logger;
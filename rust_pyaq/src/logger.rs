@@ -0,0 +1,157 @@
+//! logクレートのロガーです。
+//!
+//! ログの出力はターゲットごとにレベルで絞り込めます。主なターゲットは以下のとおりです。
+//!
+//! | ターゲット | 内容 |
+//! |------------|------|
+//! | search | 探索の結果と候補手の一覧 |
+//! | board | 盤面図 |
+//! | gtp | 受信したGTPコマンド |
+//! | selfplay | 自己対戦の結果 |
+//!
+//! レベルの指定はRUST_LOGと同様に、"info,search=warn,board=debug"のように
+//! デフォルトのレベルとターゲットごとのレベルをカンマで区切って並べます。
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+use log::{self, Level, LevelFilter, Log, Metadata, Record};
+
+/// ログのレベルの指定です。
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogSpec {
+    default: LevelFilter,
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl Default for LogSpec {
+    fn default() -> Self {
+        Self {
+            default: LevelFilter::Info,
+            targets: Vec::new(),
+        }
+    }
+}
+
+impl FromStr for LogSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut spec = Self::default();
+        for item in s.split(',').map(|item| item.trim()).filter(|item| !item.is_empty()) {
+            let mut kv = item.splitn(2, '=');
+            let key = kv.next().unwrap().trim();
+            let parse_level = |level: &str| {
+                level
+                    .trim()
+                    .parse::<LevelFilter>()
+                    .map_err(|_| format!("unknown level {}", level.trim()))
+            };
+            match kv.next() {
+                Some(level) => spec.targets.push((key.to_string(), parse_level(level)?)),
+                None => spec.default = parse_level(key)?,
+            }
+        }
+        Ok(spec)
+    }
+}
+
+impl LogSpec {
+    /// ターゲットtargetのレベルを返します。
+    /// 最も長く一致するターゲットの指定を使い、なければデフォルトのレベルです。
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .filter(|&&(ref name, _)| {
+                target == name || target.starts_with(&format!("{}::", name))
+            })
+            .max_by_key(|&&(ref name, _)| name.len())
+            .map_or(self.default, |&(_, level)| level)
+    }
+
+    /// すべてのターゲットの中で最も詳細なレベルを返します。
+    pub fn max_level(&self) -> LevelFilter {
+        self.targets
+            .iter()
+            .map(|&(_, level)| level)
+            .fold(self.default, |a, b| a.max(b))
+    }
+}
+
+/// 標準エラー出力かファイルにログを書き出すロガーです。
+struct Logger {
+    spec: LogSpec,
+    file: Option<Mutex<File>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.spec.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        // info以下は以前のeprintln!と同じ見た目にするため、レベルとターゲットをつけない
+        let line = match record.level() {
+            Level::Error | Level::Warn => {
+                format!("{} {}: {}\n", record.level(), record.target(), record.args())
+            }
+            _ => format!("{}\n", record.args()),
+        };
+        match self.file {
+            Some(ref file) => {
+                let _ = file.lock().unwrap().write_all(line.as_bytes());
+            }
+            None => {
+                let _ = io::stderr().write_all(line.as_bytes());
+            }
+        }
+    }
+
+    fn flush(&self) {
+        match self.file {
+            Some(ref file) => {
+                let _ = file.lock().unwrap().flush();
+            }
+            None => {
+                let _ = io::stderr().flush();
+            }
+        }
+    }
+}
+
+/// レベルの指定specでロガーを設定します。fileを指定するとファイルに追記します。
+pub fn init(spec: LogSpec, file: Option<&Path>) -> Result<(), String> {
+    let file = match file {
+        Some(path) => Some(Mutex::new(
+            ::std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| format!("{}: {}", path.display(), e))?,
+        )),
+        None => None,
+    };
+    log::set_max_level(spec.max_level());
+    log::set_boxed_logger(Box::new(Logger {
+        spec: spec,
+        file: file,
+    })).map_err(|e| e.to_string())
+}
+
+#[test]
+fn test_log_spec() {
+    let spec: LogSpec = "warn, search=debug,board=off".parse().unwrap();
+    assert_eq!(spec.level_for("gtp"), LevelFilter::Warn);
+    assert_eq!(spec.level_for("search"), LevelFilter::Debug);
+    assert_eq!(spec.level_for("search::tree"), LevelFilter::Debug);
+    assert_eq!(spec.level_for("searcher"), LevelFilter::Warn);
+    assert_eq!(spec.level_for("board"), LevelFilter::Off);
+    assert_eq!(spec.max_level(), LevelFilter::Debug);
+    assert_eq!("".parse::<LogSpec>().unwrap(), LogSpec::default());
+    assert!("search=loud".parse::<LogSpec>().is_err());
+}
@@ -1,7 +1,13 @@
 #![feature(test)]
 #![feature(iterator_step_by)]
 extern crate getopts;
+#[macro_use]
+extern crate log;
+extern crate rand;
 extern crate rust_pyaq;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
 extern crate sgf;
 extern crate tensorflow;
 extern crate test;
@@ -11,114 +17,562 @@ extern crate test;
 
 mod neural_network;
 mod gtp;
+mod convert;
+mod match_runner;
+mod review;
+mod self_play;
+mod eval;
+mod bench;
 
+use std::path::{Path, PathBuf};
 use rust_pyaq as rpl;
 use rpl::*;
-use rpl::constants::*;
-use rpl::board::*;
+use rpl::engine_config::{EngineConfig, LogSection};
+use rpl::board::Board;
+use rpl::evaluator::{CachedEvaluator, RolloutEvaluator, RolloutPolicy, SymmetricEvaluator,
+                    SymmetryMode};
+use rpl::logger::{self, LogSpec};
+use rpl::resign::ResignPolicy;
+use rpl::search::{Evaluate, Tree};
+use rpl::search_config::SearchConfig;
+use rpl::time_control::Overtime;
+use neural_network::NeuralNetwork;
 
-enum LaunchMode {
-    Gtp,
-    SelfPlay,
+/// サブコマンドの名前と説明です。
+const COMMANDS: [(&str, &str); 7] = [
+    ("gtp", "Play via GTP on stdin/stdout. This is the default."),
+    ("selfplay", "Play self play games and write training data."),
+    ("match", "Play games between two engines and estimate the Elo difference."),
+    ("review", "Annotate an SGF file with win rates and mistakes."),
+    ("eval", "Print the network output and search result for a position as JSON."),
+    ("convert", "Convert SGF files into training data."),
+    ("bench", "Measure the speed of board operations, evaluations and search."),
+];
+
+fn print_commands(program: &str) {
+    println!("Usage: {} [COMMAND] [options]\n\nCommands:", program);
+    for &(name, desc) in COMMANDS.iter() {
+        println!("    {:10}{}", name, desc);
+    }
+    println!("\nRun \"{} COMMAND -h\" for the options of each command.", program);
+}
+
+/// 各サブコマンドで共通のエンジンのオプションを追加します。
+fn add_engine_opts(opts: &mut getopts::Options) {
+    opts.optopt("", "config", "Engine configuration file (TOML).", "FILE")
+        .optopt("", "model", "Model file. default: frozen_model.pb", "FILE")
+        .optopt("", "main_time", "Main time(sec) defaut: 0", "NUM")
+        .optopt("", "byoyomi", "Byoyomi(sec) default: 3 (1 for self play)", "NUM")
+        .optopt("", "byoyomi_periods", "Japanese byoyomi periods. default: 1", "NUM")
+        .optopt(
+            "",
+            "canadian_stones",
+            "Stones per Canadian overtime period. 0 means Japanese byoyomi. default: 0",
+            "NUM",
+        )
+        .optflag(
+            "",
+            "reallocate_time",
+            "Give time saved by early termination to the following moves.",
+        )
+        .optopt("", "lag", "Safety margin(sec) for network latency. default: 0.2", "NUM")
+        .optopt(
+            "",
+            "time_extension",
+            "Max extension of search time when the best move is unstable (ratio). default: 1",
+            "NUM",
+        )
+        .optopt("", "playouts", "Playouts per move. 0 means time control. default: 0", "NUM")
+        .optopt("", "seed", "Random seed for reproducible games.", "NUM")
+        .optopt(
+            "",
+            "symmetry",
+            "Board symmetry for network evaluation: none, random or average. default: none",
+            "MODE",
+        )
+        .optopt(
+            "",
+            "cache_size",
+            "Number of network evaluations to cache. 0 disables. default: 16384",
+            "NUM",
+        )
+        .optopt(
+            "",
+            "rollouts",
+            "Evaluate positions by this many random rollouts instead of the network. \
+             0 uses the network. default: 0",
+            "NUM",
+        )
+        .optopt(
+            "",
+            "rollout_policy",
+            "Move priors with --rollouts: uniform or heuristic. default: heuristic",
+            "MODE",
+        )
+        .optopt(
+            "",
+            "resign_threshold",
+            "Resign when win rate is below this. 0 disables. default: 0.1 (0.05 for self play)",
+            "NUM",
+        )
+        .optopt("", "resign_min_move", "Minimum move number to resign. default: 0", "NUM")
+        .optopt(
+            "",
+            "resign_consecutive",
+            "Consecutive moves below the threshold to resign. default: 1",
+            "NUM",
+        );
+    for &(name, desc) in search_config::PARAMS.iter() {
+        opts.optopt("", name, desc, "VALUE");
+    }
+    add_log_opts(opts);
 }
 
-fn print_usage(program: &str, opts: getopts::Options) {
-    let brief = format!("Usage: {} [options]", program);
-    print!("{}", opts.usage(&brief));
+/// ログのオプションを追加します。
+fn add_log_opts(opts: &mut getopts::Options) {
+    opts.optopt(
+        "",
+        "log",
+        "Log levels such as \"info,search=warn,board=debug\". \
+         Targets are search, board, gtp, selfplay, review and convert. \
+         default: info (warn for bench)",
+        "SPEC",
+    ).optopt("", "log_file", "Append logs to this file instead of stderr.", "FILE")
+        .optflag("q", "quiet", "Only log warnings and errors. Same as --log warn.");
 }
 
-fn make_opts() -> getopts::Options {
+/// argsを解析します。-hの時は使い方を表示して終了し、不正な引数の時はエラーを表示して終了します。
+fn parse_args(opts: &getopts::Options, args: &[String], brief: &str) -> getopts::Matches {
+    let matches = match opts.parse(args) {
+        Ok(m) => m,
+        Err(f) => {
+            eprintln!("{}\n\n{}", f, opts.short_usage(brief.lines().next().unwrap_or("")));
+            std::process::exit(1);
+        }
+    };
+    if matches.opt_present("h") {
+        print!("{}", opts.usage(brief));
+        std::process::exit(0);
+    }
+    matches
+}
+
+/// オプションnameの値を返します。指定がなければNoneです。
+/// 値を解釈できなければエラーを表示して終了します。
+fn parse_opt_value<T: std::str::FromStr>(matches: &getopts::Matches, name: &str) -> Option<T> {
+    matches.opt_str(name).map(|s| {
+        s.parse().unwrap_or_else(|_| {
+            eprintln!("--{}: invalid value {}", name, s);
+            std::process::exit(1);
+        })
+    })
+}
+
+/// オプションnameの値を返します。指定がなければdefaultです。
+fn parse_opt<T: std::str::FromStr>(matches: &getopts::Matches, name: &str, default: T) -> T {
+    parse_opt_value(matches, name).unwrap_or(default)
+}
+
+/// configをコマンドライン引数で上書きした探索パラメータを返します。
+fn make_search_config(matches: &getopts::Matches, mut config: SearchConfig) -> SearchConfig {
+    for &(name, _) in search_config::PARAMS.iter() {
+        if let Some(value) = matches.opt_str(name) {
+            if let Err(e) = config.set(name, &value) {
+                eprintln!("--{}: {}", name, e);
+                std::process::exit(1);
+            }
+        }
+    }
+    config
+}
+
+/// argsからログのオプションと設定ファイルのオプションだけを取り出して解析します。
+/// サブコマンドの引数を解析する前にロガーを初期化するためで、その他の引数は無視します。
+fn parse_log_args(args: &[String]) -> getopts::Matches {
     let mut opts = getopts::Options::new();
-    opts.optflag("h", "help", "Help")
-        .optflag("", "self", "Self play.")
-        .optflag("", "quick", "No MCTS.")
-        .optflag("", "random", "Random play.")
-        // .optflag("", "cpu", "CPU only, no GPUs.") // 現状、
-        .optflag("", "clean", "Try to pickup all dead stones.")
-        .optopt("", "main_time", "Main time(sec) defaut: 0", "NUM")
-        .optopt("", "byoyomi", "Byoyomi(sec) default: 3 (1 for self play)", "NUM");
-    opts
-}
-
-fn random_self_play(max_move_cnt: usize) -> Board {
-    let mut b = Board::new();
-    while b.get_move_cnt() < max_move_cnt {
-        let prev_move = b.get_prev_move();
-        let mov = b.random_play();
-        let _ = b.play(mov, false);
-        b.showboard();
-        if prev_move == PASS && mov == PASS {
-            break;
+    opts.optopt("", "config", "", "FILE");
+    add_log_opts(&mut opts);
+    let mut log_args = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--config" || arg == "--log" || arg == "--log_file" {
+            log_args.push(arg.clone());
+            log_args.extend(iter.next().cloned());
+        } else if arg.starts_with("--config=") || arg.starts_with("--log=")
+            || arg.starts_with("--log_file=") || arg == "--quiet" || arg == "-q"
+        {
+            log_args.push(arg.clone());
         }
     }
-    b
+    opts.parse(&log_args).unwrap_or_else(|f| {
+        eprintln!("{}", f);
+        std::process::exit(1);
+    })
+}
+
+/// ロガーを初期化します。
+/// レベルは--log, --quiet, 環境変数RSAQ_LOG, 設定ファイル, default_specの順に優先します。
+fn init_logger(matches: &getopts::Matches, config: &LogSection, default_spec: LogSpec) {
+    let spec = matches
+        .opt_str("log")
+        .or(if matches.opt_present("quiet") {
+            Some("warn".to_string())
+        } else {
+            None
+        })
+        .or(std::env::var("RSAQ_LOG").ok())
+        .or(config.level.clone());
+    let spec = match spec {
+        Some(spec) => spec.parse::<LogSpec>().unwrap_or_else(|e| {
+            eprintln!("--log: {}", e);
+            std::process::exit(1);
+        }),
+        None => default_spec,
+    };
+    let file = matches.opt_str("log_file").map(PathBuf::from).or(config.file.clone());
+    if let Err(e) = logger::init(spec, file.as_ref().map(|p| p.as_path())) {
+        eprintln!("--log_file: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// エンジンが局面の評価に使う評価器です。
+pub enum EngineEvaluator {
+    Network(NeuralNetwork),
+    Rollout(RolloutEvaluator),
 }
 
-fn self_play(max_move_cnt: usize, time: f32, clean: bool) -> Board {
-    use neural_network::NeuralNetwork;
+impl EngineEvaluator {
+    /// rollout_cntが0より大きければロールアウトの評価器を、そうでなければmodelのネットワークを作成します。
+    pub fn new(model: &Path, rollout_cnt: usize, policy: RolloutPolicy) -> Self {
+        if rollout_cnt > 0 {
+            let mut evaluator = RolloutEvaluator::new(rollout_cnt);
+            evaluator.set_policy(policy);
+            EngineEvaluator::Rollout(evaluator)
+        } else {
+            EngineEvaluator::Network(NeuralNetwork::new(model))
+        }
+    }
+
+    /// ロールアウトの乱数生成器をシードseedで初期化します。
+    pub fn set_seed(&mut self, seed: u64) {
+        if let EngineEvaluator::Rollout(ref mut evaluator) = *self {
+            evaluator.set_seed(seed);
+        }
+    }
+}
 
-    let mut b = Board::new();
-    let mut tree = search::Tree::new(NeuralNetwork::new("frozen_model.pb"));
-    while b.get_move_cnt() < max_move_cnt {
-        let prev_move = b.get_prev_move();
-        let (mov, _) = tree.search(&b, time, false, clean);
-        let _ = b.play(mov, false);
-        b.showboard();
-        if prev_move == PASS && mov == PASS {
-            break;
+impl Evaluate for EngineEvaluator {
+    fn evaluate(&mut self, board: &Board) -> (Vec<f32>, Vec<f32>) {
+        match *self {
+            EngineEvaluator::Network(ref mut nn) => nn.evaluate(board),
+            EngineEvaluator::Rollout(ref mut evaluator) => evaluator.evaluate(board),
         }
     }
-    b
+}
+
+/// add_engine_optsのオプションと設定ファイルから決めたエンジンの設定です。
+#[derive(Clone)]
+struct EngineSettings {
+    engine_config: EngineConfig,
+    model: PathBuf,
+    main_time: f32,
+    overtime: Overtime,
+    lag: f32,
+    time_extension: f32,
+    reallocate_time: bool,
+    max_playout: usize,
+    seed: Option<u64>,
+    symmetry: SymmetryMode,
+    cache_size: usize,
+    rollouts: usize,
+    rollout_policy: RolloutPolicy,
+    config: SearchConfig,
+    resign: ResignPolicy,
+}
+
+impl EngineSettings {
+    /// 設定ファイルの値をデフォルト値とし、コマンドライン引数で上書きした設定を返します。
+    /// self_play_modeの時は自己対戦用のデフォルト値を使います。
+    fn new(matches: &getopts::Matches, self_play_mode: bool) -> Self {
+        let engine_config = match matches.opt_str("config") {
+            Some(path) => EngineConfig::load(&path).unwrap_or_else(|e| {
+                eprintln!("--config: {}", e);
+                std::process::exit(1);
+            }),
+            None => EngineConfig::default(),
+        };
+        let model = matches
+            .opt_str("model")
+            .map(PathBuf::from)
+            .unwrap_or(engine_config.model_path());
+        let time_config = engine_config.time.clone();
+        let main_time = parse_opt(matches, "main_time", time_config.main_time.unwrap_or(0.0));
+        let byoyomi = parse_opt(
+            matches,
+            "byoyomi",
+            time_config
+                .byoyomi
+                .unwrap_or(if self_play_mode { 1.0 } else { 3.0 }),
+        );
+        let canadian_stones = parse_opt(
+            matches,
+            "canadian_stones",
+            time_config.canadian_stones.unwrap_or(0),
+        );
+        let overtime = if canadian_stones > 0 {
+            Overtime::Canadian {
+                time: byoyomi,
+                stones: canadian_stones,
+            }
+        } else {
+            Overtime::Byoyomi {
+                time: byoyomi,
+                periods: parse_opt(
+                    matches,
+                    "byoyomi_periods",
+                    time_config.byoyomi_periods.unwrap_or(1),
+                ),
+            }
+        };
+        let symmetry = match matches.opt_str("symmetry") {
+            Some(s) => s.parse().unwrap_or_else(|e| {
+                eprintln!("--symmetry: {}", e);
+                std::process::exit(1);
+            }),
+            None => SymmetryMode::None,
+        };
+        let rollout_policy = match matches
+            .opt_str("rollout_policy")
+            .or(engine_config.rollout_policy.clone())
+        {
+            Some(s) => s.parse().unwrap_or_else(|e| {
+                eprintln!("--rollout_policy: {}", e);
+                std::process::exit(1);
+            }),
+            None => RolloutPolicy::Heuristic,
+        };
+        let mut config = SearchConfig::default();
+        if self_play_mode {
+            // 自己対戦ではルートにノイズを混ぜ、序盤は確率的に着手して棋譜を多様にする
+            config.noise_epsilon = 0.25;
+            config.temperature_moves = 10;
+            // 訪問回数の分布を学習データにするので、最初に見つかった手に偏らないよう打ち切らない
+            config.early_stop = false;
+        }
+        let config = engine_config
+            .search_config(config)
+            .unwrap_or_else(|e| {
+                eprintln!("--config: {}", e);
+                std::process::exit(1);
+            });
+        let resign_config = engine_config.resign.clone();
+        let resign = ResignPolicy::new(
+            parse_opt(
+                matches,
+                "resign_threshold",
+                resign_config
+                    .threshold
+                    .unwrap_or(if self_play_mode { 0.05 } else { 0.1 }),
+            ),
+            parse_opt(matches, "resign_min_move", resign_config.min_move.unwrap_or(0)),
+            parse_opt(
+                matches,
+                "resign_consecutive",
+                resign_config.consecutive.unwrap_or(1),
+            ),
+        );
+        Self {
+            model: model,
+            main_time: main_time,
+            overtime: overtime,
+            lag: parse_opt(matches, "lag", time_config.lag.unwrap_or(0.2)),
+            time_extension: parse_opt(
+                matches,
+                "time_extension",
+                time_config.extension.unwrap_or(1.0),
+            ),
+            reallocate_time: matches.opt_present("reallocate_time")
+                || time_config.reallocate.unwrap_or(false),
+            max_playout: parse_opt(matches, "playouts", engine_config.playouts.unwrap_or(0)),
+            seed: parse_opt_value(matches, "seed"),
+            symmetry: symmetry,
+            cache_size: parse_opt(matches, "cache_size", 16384),
+            rollouts: parse_opt(matches, "rollouts", engine_config.rollouts.unwrap_or(0)),
+            rollout_policy: rollout_policy,
+            config: make_search_config(matches, config),
+            resign: resign,
+            engine_config: engine_config,
+        }
+    }
+
+    /// ネットワークを使うのにモデルファイルがなければエラーを表示して終了します。
+    fn check_model(&self) {
+        if self.rollouts == 0 && !self.model.exists() {
+            eprintln!(
+                "model: {} not found (use --rollouts to play without a network)",
+                self.model.display()
+            );
+            std::process::exit(1);
+        }
+    }
+
+    fn make_tree(&self) -> Tree<CachedEvaluator<SymmetricEvaluator<EngineEvaluator>>> {
+        let nn = EngineEvaluator::new(&self.model, self.rollouts, self.rollout_policy);
+        let nn = SymmetricEvaluator::new(nn, self.symmetry);
+        let mut tree = Tree::new(CachedEvaluator::new(nn, self.cache_size));
+        {
+            let time_manager = tree.time_manager_mut();
+            time_manager.set_time_settings(self.main_time, self.overtime);
+            time_manager.lag = self.lag;
+            time_manager.max_extension = self.time_extension;
+            time_manager.reallocate = self.reallocate_time;
+        }
+        tree.set_max_playout(self.max_playout);
+        tree.set_config(self.config.clone());
+        if let Some(seed) = self.seed {
+            // 乱数列が相関しないよう、部品ごとにシードをずらす
+            tree.set_seed(seed);
+            tree.nn.inner.set_seed(seed.wrapping_add(1));
+            tree.nn.inner.inner.set_seed(seed.wrapping_add(2));
+        }
+        tree
+    }
+}
+
+/// gtpサブコマンドを実行します。
+fn gtp_main(program: &str, args: &[String]) {
+    let mut opts = getopts::Options::new();
+    opts.optflag("h", "help", "Help")
+        .optflag("", "quick", "No MCTS.")
+        // .optflag("", "cpu", "CPU only, no GPUs.") // 現状、
+        .optflag("", "clean", "Try to pickup all dead stones.");
+    add_engine_opts(&mut opts);
+    let matches = parse_args(&opts, args, &format!("Usage: {} [gtp] [options]", program));
+    let settings = EngineSettings::new(&matches, false);
+    settings.check_model();
+    // let use_gpu = !matches.opt_present("cpu");
+    gtp::GtpClient::new(
+        settings.make_tree(),
+        matches.opt_present("quick"),
+        matches.opt_present("clean"),
+        settings.resign.clone(),
+    ).call_gtp();
+}
+
+/// convertサブコマンドを実行します。
+fn convert_main(program: &str, args: &[String]) {
+    let mut opts = getopts::Options::new();
+    opts.optflag("h", "help", "Help")
+        .optopt("", "out", "Output directory. default: data", "DIR")
+        .optflag("", "augment", "Augment samples with the 8 symmetries.")
+        .optopt("", "chunk_size", "Samples per chunk. default: 4096", "NUM");
+    add_log_opts(&mut opts);
+    let brief = format!("Usage: {} convert [options] SGF_DIR", program);
+    let matches = parse_args(&opts, args, &brief);
+    if matches.free.len() != 1 {
+        print!("{}", opts.usage(&brief));
+        std::process::exit(1);
+    }
+    let out = matches.opt_str("out").unwrap_or("data".to_string());
+    let result = convert::convert(
+        Path::new(&matches.free[0]),
+        Path::new(&out),
+        matches.opt_present("augment"),
+        parse_opt(&matches, "chunk_size", 4096),
+    );
+    if let Err(e) = result {
+        eprintln!("convert: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// reviewサブコマンドを実行します。
+fn review_main(program: &str, args: &[String]) {
+    let mut opts = getopts::Options::new();
+    opts.optflag("h", "help", "Help")
+        .optopt(
+            "",
+            "threshold",
+            "Mark moves losing this much win rate as mistakes. default: 0.1",
+            "NUM",
+        )
+        .optopt("", "out", "Output file. default: FILE_review.sgf", "FILE");
+    add_engine_opts(&mut opts);
+    let brief = format!(
+        "Usage: {} review [options] FILE.sgf\n\n\
+         Positions are searched with --playouts playouts (1600 if not given).",
+        program
+    );
+    let matches = parse_args(&opts, args, &brief);
+    if matches.free.len() != 1 {
+        print!("{}", opts.usage(&brief));
+        std::process::exit(1);
+    }
+    let input = Path::new(&matches.free[0]);
+    let output = match matches.opt_str("out") {
+        Some(out) => PathBuf::from(out),
+        None => input.with_file_name(format!(
+            "{}_review.sgf",
+            input.file_stem().unwrap_or_default().to_string_lossy()
+        )),
+    };
+    let settings = EngineSettings::new(&matches, false);
+    settings.check_model();
+    let mut tree = settings.make_tree();
+    if settings.max_playout == 0 {
+        tree.set_max_playout(1600);
+    }
+    let threshold = parse_opt(&matches, "threshold", 0.1);
+    if let Err(e) = review::review(&mut tree, input, &output, threshold) {
+        eprintln!("review: {}", e);
+        std::process::exit(1);
+    }
+    eprintln!("wrote {}", output.display());
 }
 
 fn main() {
-    let opts = make_opts();
     let args: Vec<String> = std::env::args().collect();
-    let matches = match opts.parse(&args[1..]) {
-        Ok(m) => m,
-        Err(f) => panic!(f.to_string()),
+    let program = &args[0];
+    // サブコマンドを省略した場合はGTPとして動作する。--selfは以前のオプションとの互換のため
+    let (command, rest): (&str, Vec<String>) = match args.get(1) {
+        Some(arg) if !arg.starts_with('-') => (arg, args[2..].to_vec()),
+        Some(arg) if arg == "-h" || arg == "--help" => ("help", Vec::new()),
+        _ if args.iter().any(|a| a == "--self") => (
+            "selfplay",
+            args[1..].iter().filter(|a| *a != "--self").cloned().collect(),
+        ),
+        _ => ("gtp", args[1..].to_vec()),
     };
-    if matches.opt_present("h") {
-        print_usage(&args[0], opts);
-        std::process::exit(0);
-    }
-    let launch_mode = if matches.opt_present("self") {
-        LaunchMode::SelfPlay
+    // どのサブコマンドでもログの設定が効くよう、サブコマンドに分岐する前にロガーを初期化する
+    let log_matches = parse_log_args(&rest);
+    let log_config = match log_matches.opt_str("config") {
+        Some(path) => EngineConfig::load(&path).map(|c| c.log).unwrap_or_else(|e| {
+            eprintln!("--config: {}", e);
+            std::process::exit(1);
+        }),
+        None => LogSection::default(),
+    };
+    // ベンチマークでは探索ごとのログが計測の邪魔になるので、既定では警告以上だけにする
+    let default_spec = if command == "bench" {
+        "warn".parse().unwrap()
     } else {
-        LaunchMode::Gtp
+        LogSpec::default()
     };
-    let quick = matches.opt_present("quick");
-    let random = matches.opt_present("random");
-    let clean = matches.opt_present("clean");
-    let main_time = matches
-        .opt_str("main_time")
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(0.0);
-    let byoyomi = matches
-        .opt_str("byoyomi")
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(3.0);
-    // let use_gpu = !matches.opt_present("cpu");
-
-    match launch_mode {
-        LaunchMode::Gtp => {
-            gtp::GtpClient::new(main_time, byoyomi, quick, clean).call_gtp();
-        }
-        LaunchMode::SelfPlay => {
-            let end_position = if random {
-                random_self_play(BVCNT * 2)
-            } else {
-                self_play(BVCNT * 2, 0.0, clean)
-            };
-
-            let score = end_position.final_score();
-            let result_str = if score == 0.0 {
-                "Draw".to_string()
-            } else {
-                let winner = if score > 0.0 { "B" } else { "W" };
-                format!("{}+{:.1}", winner, score.abs())
-            };
-            eprintln!("result: {}", result_str);
+    init_logger(&log_matches, &log_config, default_spec);
+    match command {
+        "gtp" => gtp_main(program, &rest),
+        "selfplay" => self_play::selfplay_main(program, &rest),
+        "match" => match_runner::match_main(program, &rest),
+        "review" => review_main(program, &rest),
+        "eval" => eval::eval_main(program, &rest),
+        "convert" => convert_main(program, &rest),
+        "bench" => bench::bench_main(program, &rest),
+        "help" => print_commands(program),
+        _ => {
+            eprintln!("unknown command: {}\n", command);
+            print_commands(program);
+            std::process::exit(1);
         }
     }
 }
@@ -0,0 +1,437 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use getopts;
+use constants::*;
+use intersection::Color;
+use board::Board;
+use search::Tree;
+use resign::ResignPolicy;
+use evaluator::{CachedEvaluator, SymmetricEvaluator};
+use elo::{MatchStats, Sprt, SprtResult};
+use sgf_writer::to_sgf;
+use gtp_controller::GtpController;
+use {add_engine_opts, parse_args, parse_opt, EngineEvaluator, EngineSettings};
+
+/// 対局者です。
+pub trait Player {
+    fn name(&self) -> &str;
+
+    /// 新しい対局を始めます。
+    fn new_game(&mut self);
+
+    /// 局面bでの着手を返します。投了する場合はNoneを返します。
+    fn genmove(&mut self, b: &Board) -> Option<usize>;
+}
+
+/// 設定keyの値valueをT型として返します。
+fn parse_value<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("{}: invalid value", key))
+}
+
+/// 同じプロセス内で探索する対局者です。
+pub struct Engine {
+    name: String,
+    tree: Tree<CachedEvaluator<SymmetricEvaluator<EngineEvaluator>>>,
+    resign: ResignPolicy,
+}
+
+impl Engine {
+    /// "model=frozen_model.pb,playouts=800,c_puct=1.5"のような、カンマ区切りのkey=valueの設定で
+    /// 共通の設定baseを上書きして作成します。
+    /// keyはname, model, playouts, rollouts, rollout_policy, symmetry, cache_size, resignと
+    /// 探索パラメータの名前です。rolloutsを指定するとネットワークの代わりにロールアウトで評価します。
+    pub fn from_spec(spec: &str, base: &EngineSettings) -> Result<Self, String> {
+        let mut settings = base.clone();
+        let mut name = None;
+        for item in spec.split(',').filter(|s| !s.is_empty()) {
+            let mut kv = item.splitn(2, '=');
+            let (key, value) = match (kv.next(), kv.next()) {
+                (Some(key), Some(value)) => (key, value),
+                _ => return Err(format!("{}: expected key=value", item)),
+            };
+            match key {
+                "name" => name = Some(value.to_string()),
+                "model" => settings.model = PathBuf::from(value),
+                "playouts" => settings.max_playout = parse_value(key, value)?,
+                "rollouts" => settings.rollouts = parse_value(key, value)?,
+                "rollout_policy" => settings.rollout_policy = parse_value(key, value)?,
+                "symmetry" => settings.symmetry = parse_value(key, value)?,
+                "cache_size" => settings.cache_size = parse_value(key, value)?,
+                "resign" => settings.resign.threshold = parse_value(key, value)?,
+                _ => settings
+                    .config
+                    .set(key, value)
+                    .map_err(|e| format!("{}: {}", key, e))?,
+            }
+        }
+        if settings.rollouts == 0 && !settings.model.exists() {
+            return Err(format!("{}: model not found", settings.model.display()));
+        }
+
+        let default_name = if settings.rollouts > 0 {
+            let policy = format!("{:?}", settings.rollout_policy).to_lowercase();
+            format!("rollout{}-{}:{}", settings.rollouts, policy, settings.max_playout)
+        } else {
+            format!("{}:{}", settings.model.display(), settings.max_playout)
+        };
+        Ok(Self {
+            name: name.unwrap_or(default_name),
+            tree: settings.make_tree(),
+            resign: settings.resign,
+        })
+    }
+}
+
+impl Player for Engine {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn new_game(&mut self) {
+        self.tree.clear();
+        self.resign.clear();
+    }
+
+    fn genmove(&mut self, b: &Board) -> Option<usize> {
+        let (mov, win_rate) = self.tree.search(b, 0.0, false, true);
+        if self.resign.should_resign(b.get_move_cnt(), win_rate) {
+            None
+        } else {
+            Some(mov)
+        }
+    }
+}
+
+/// GTPで操作する外部のエンジンの対局者です。
+pub struct GtpPlayer {
+    name: String,
+    engine: GtpController,
+    synced_cnt: usize, // エンジンに伝えた着手の数
+}
+
+impl GtpPlayer {
+    /// コマンドラインcommandでエンジンを起動します。nameがなければエンジンの名前を使います。
+    pub fn new(command: &str, name: Option<String>) -> Result<Self, String> {
+        let mut engine = GtpController::spawn(command).map_err(|e| format!("{}: {}", command, e))?;
+        let name = match name {
+            Some(name) => name,
+            None => engine.name()?,
+        };
+        Ok(Self {
+            name: name,
+            engine: engine,
+            synced_cnt: 0,
+        })
+    }
+
+    fn try_genmove(&mut self, b: &Board) -> Result<Option<usize>, String> {
+        // 前回から進んだ分の着手を伝える
+        for (i, &mov) in b.get_history().iter().enumerate().skip(self.synced_cnt) {
+            let color = if i % 2 == 0 {
+                Color::Black
+            } else {
+                Color::White
+            };
+            self.engine.play(color, mov)?;
+        }
+        let mov = self.engine.genmove(b.get_turn())?;
+        self.synced_cnt = b.get_move_cnt() + 1;
+        Ok(mov)
+    }
+}
+
+impl Player for GtpPlayer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn new_game(&mut self) {
+        if let Err(e) = self.engine.clear_board() {
+            eprintln!("{}: {}", self.name, e);
+        }
+        self.synced_cnt = 0;
+    }
+
+    /// エンジンとの通信に失敗した場合は投了とみなします。
+    fn genmove(&mut self, b: &Board) -> Option<usize> {
+        self.try_genmove(b).unwrap_or_else(|e| {
+            eprintln!("{}: {}", self.name, e);
+            None
+        })
+    }
+}
+
+/// 同じプロセス内のエンジンか外部のGTPエンジンの対局者です。
+pub enum AnyPlayer {
+    Engine(Engine),
+    Gtp(GtpPlayer),
+}
+
+impl AnyPlayer {
+    /// 設定にgtp=COMMANDがあれば外部のエンジン、なければ共通の設定baseで同じプロセス内のエンジンを作成します。
+    pub fn from_spec(spec: &str, base: &EngineSettings) -> Result<Self, String> {
+        let mut command = None;
+        let mut name = None;
+        for item in spec.split(',') {
+            if item.starts_with("gtp=") {
+                command = Some(&item[4..]);
+            } else if item.starts_with("name=") {
+                name = Some(item[5..].to_string());
+            }
+        }
+        match command {
+            Some(command) => GtpPlayer::new(command, name).map(AnyPlayer::Gtp),
+            None => Engine::from_spec(spec, base).map(AnyPlayer::Engine),
+        }
+    }
+}
+
+impl Player for AnyPlayer {
+    fn name(&self) -> &str {
+        match *self {
+            AnyPlayer::Engine(ref p) => p.name(),
+            AnyPlayer::Gtp(ref p) => p.name(),
+        }
+    }
+
+    fn new_game(&mut self) {
+        match *self {
+            AnyPlayer::Engine(ref mut p) => p.new_game(),
+            AnyPlayer::Gtp(ref mut p) => p.new_game(),
+        }
+    }
+
+    fn genmove(&mut self, b: &Board) -> Option<usize> {
+        match *self {
+            AnyPlayer::Engine(ref mut p) => p.genmove(b),
+            AnyPlayer::Gtp(ref mut p) => p.genmove(b),
+        }
+    }
+}
+
+/// 1局の結果です。
+pub struct GameResult {
+    pub history: Vec<usize>,
+    pub winner: Option<Color>,
+    /// SGFのREプロパティの形式の結果です。
+    pub result: String,
+}
+
+fn color2str(color: Color) -> &'static str {
+    match color {
+        Color::Black => "B",
+        Color::White => "W",
+    }
+}
+
+/// blackとwhiteで1局対局します。
+/// 投了、反則、連続パスまたは手数の上限で終局し、投了と反則以外は終局図の面積で判定します。
+pub fn play_game<B: Player, W: Player>(black: &mut B, white: &mut W) -> GameResult {
+    black.new_game();
+    white.new_game();
+    let mut b = Board::new();
+    let mut loser = None;
+    let mut reason = "";
+    while b.get_move_cnt() < BVCNT * 2 {
+        let turn = b.get_turn();
+        let mov = match turn {
+            Color::Black => black.genmove(&b),
+            Color::White => white.genmove(&b),
+        };
+        let prev_move = b.get_prev_move();
+        match mov {
+            Some(mov) => if b.play(mov, false).is_err() {
+                loser = Some(turn);
+                reason = "F";
+                break;
+            } else if prev_move == PASS && mov == PASS {
+                break;
+            },
+            None => {
+                loser = Some(turn);
+                reason = "R";
+                break;
+            }
+        }
+    }
+    let (winner, result) = match loser {
+        Some(color) => (
+            Some(color.opponent()),
+            format!("{}+{}", color2str(color.opponent()), reason),
+        ),
+        None => {
+            let score = b.area_score();
+            if score == 0.0 {
+                (None, "0".to_string())
+            } else {
+                let winner = if score > 0.0 {
+                    Color::Black
+                } else {
+                    Color::White
+                };
+                (Some(winner), format!("{}+{}", color2str(winner), score.abs()))
+            }
+        }
+    };
+    GameResult {
+        history: b.get_history().clone(),
+        winner: winner,
+        result: result,
+    }
+}
+
+/// p1とp2で手番を交互に入れ替えてgames局対局し、p1から見た成績を返します。
+/// sgf_dirがあれば各局の棋譜を保存し、sprtがあれば判定がついた時点で打ち切ります。
+pub fn run_match<P1: Player, P2: Player>(
+    p1: &mut P1,
+    p2: &mut P2,
+    games: usize,
+    sgf_dir: Option<&Path>,
+    sprt: Option<Sprt>,
+) -> io::Result<MatchStats> {
+    if let Some(dir) = sgf_dir {
+        fs::create_dir_all(dir)?;
+    }
+    let mut stats = MatchStats::default();
+    for i in 0..games {
+        let p1_color = if i % 2 == 0 {
+            Color::Black
+        } else {
+            Color::White
+        };
+        let (game, black, white) = if p1_color == Color::Black {
+            (play_game(p1, p2), p1.name(), p2.name())
+        } else {
+            (play_game(p2, p1), p2.name(), p1.name())
+        };
+        stats.add(game.winner.map(|c| c == p1_color));
+        eprintln!(
+            "game {}: {}(B) vs {}(W): {}",
+            i + 1,
+            black,
+            white,
+            game.result
+        );
+        if let Some(dir) = sgf_dir {
+            let path = dir.join(format!("game_{:04}.sgf", i + 1));
+            let mut file = fs::File::create(path)?;
+            file.write_all(to_sgf(&game.history, black, white, &game.result).as_bytes())?;
+        }
+        report(p1.name(), p2.name(), &stats, sprt.as_ref());
+        if let Some(ref sprt) = sprt {
+            if sprt.judge(&stats) != SprtResult::Continue {
+                break;
+            }
+        }
+    }
+    Ok(stats)
+}
+
+/// 成績とEloの推定値を95%信頼区間とともに表示します。
+fn report(name1: &str, name2: &str, stats: &MatchStats, sprt: Option<&Sprt>) {
+    const Z: f32 = 1.96;
+    let (score_low, score_high) = stats.score_interval(Z);
+    let (elo_low, elo_high) = stats.elo_interval(Z);
+    eprintln!(
+        "{} vs {}: +{} -{} ={} score {:.1}% [{:.1}%, {:.1}%] elo {:+.1} [{:+.1}, {:+.1}]",
+        name1,
+        name2,
+        stats.wins,
+        stats.losses,
+        stats.draws,
+        stats.score() * 100.0,
+        score_low * 100.0,
+        score_high * 100.0,
+        stats.elo(),
+        elo_low,
+        elo_high
+    );
+    if let Some(sprt) = sprt {
+        let (lower, upper) = sprt.bounds();
+        let judge = match sprt.judge(stats) {
+            SprtResult::Continue => "continue",
+            SprtResult::AcceptH0 => "H0 accepted",
+            SprtResult::AcceptH1 => "H1 accepted",
+        };
+        eprintln!(
+            "sprt elo0={} elo1={}: llr {:.2} [{:.2}, {:.2}] {}",
+            sprt.elo0,
+            sprt.elo1,
+            sprt.llr(stats),
+            lower,
+            upper,
+            judge
+        );
+    }
+}
+
+/// matchサブコマンドを実行します。
+pub fn match_main(program: &str, args: &[String]) {
+    let mut opts = getopts::Options::new();
+    opts.optflag("h", "help", "Help")
+        .optopt("", "games", "Number of games. default: 100", "NUM")
+        .optopt("", "sgf_dir", "Directory to save game records. default: match", "DIR")
+        .optopt(
+            "",
+            "sprt",
+            "Stop early by SPRT with hypotheses elo0 and elo1 of ENGINE1.",
+            "ELO0,ELO1",
+        )
+        .optopt("", "alpha", "SPRT type I error. default: 0.05", "NUM")
+        .optopt("", "beta", "SPRT type II error. default: 0.05", "NUM");
+    add_engine_opts(&mut opts);
+    let brief = format!(
+        "Usage: {} match [options] ENGINE1 ENGINE2\n\n\
+         ENGINE is comma separated key=value settings such as \
+         \"model=frozen_model.pb,playouts=800,c_puct=1.5\".\n\
+         Keys are name, model, playouts, rollouts, rollout_policy, symmetry, cache_size, \
+         resign and search parameters. They override the engine options below, \
+         and playouts is 800 if not given anywhere.\n\
+         An external GTP engine is given by \"gtp=COMMAND\" such as \
+         \"gtp=gnugo --mode gtp --level 10\".",
+        program
+    );
+    let matches = parse_args(&opts, args, &brief);
+    if matches.free.len() != 2 {
+        print!("{}", opts.usage(&brief));
+        std::process::exit(1);
+    }
+    let mut settings = EngineSettings::new(&matches, false);
+    if settings.max_playout == 0 {
+        settings.max_playout = 800;
+    }
+    let sprt = matches.opt_str("sprt").map(|s| {
+        let elos = s.split(',').map(|e| e.trim().parse()).collect::<Result<Vec<f32>, _>>();
+        let elos = elos.unwrap_or_default();
+        if elos.len() != 2 {
+            eprintln!("--sprt: expected ELO0,ELO1");
+            std::process::exit(1);
+        }
+        Sprt::new(
+            elos[0],
+            elos[1],
+            parse_opt(&matches, "alpha", 0.05),
+            parse_opt(&matches, "beta", 0.05),
+        )
+    });
+    let engine = |i: usize| {
+        // 同じシードで同じ手順にならないよう、エンジンごとにシードをずらす
+        // make_treeが部品ごとに下位のビットでずらすので、上位のビットでずらす
+        let mut base = settings.clone();
+        base.seed = settings.seed.map(|s| s.wrapping_add((i as u64) << 32));
+        AnyPlayer::from_spec(&matches.free[i], &base).unwrap_or_else(|e| {
+            eprintln!("ENGINE{}: {}", i + 1, e);
+            std::process::exit(1);
+        })
+    };
+    let mut p1 = engine(0);
+    let mut p2 = engine(1);
+    let games = parse_opt(&matches, "games", 100);
+    let sgf_dir = matches.opt_str("sgf_dir").unwrap_or("match".to_string());
+    if let Err(e) = run_match(&mut p1, &mut p2, games, Some(Path::new(&sgf_dir)), sprt) {
+        eprintln!("match: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -1,5 +1,6 @@
 use std::fs::File;
 use std::io::Read;
+use std::path::Path;
 use tensorflow as tf;
 use constants::*;
 use board::Board;
@@ -13,7 +14,7 @@ pub struct NeuralNetwork {
 
 impl NeuralNetwork {
     /// ファイル名nameのプロトコルバッファからモデルを読み込みます。
-    pub fn new(name: &str) -> Self {
+    pub fn new<P: AsRef<Path>>(name: P) -> Self {
         let mut graph = tf::Graph::new();
         let mut proto = Vec::new();
         File::open(name).unwrap().read_to_end(&mut proto).unwrap();
@@ -60,14 +61,17 @@ impl Evaluate for NeuralNetwork {
 mod tests {
     use test::Bencher;
 
+    /// モデルファイルは環境変数RSAQ_MODELで指定します。指定がなければDEFAULT_MODELです。
     #[bench]
     fn bench_search_branch(b: &mut Bencher) {
         use board::Board;
+        use engine_config::DEFAULT_MODEL;
         use neural_network::NeuralNetwork;
         use search::{Evaluate, Tree};
 
+        let model = std::env::var("RSAQ_MODEL").unwrap_or(DEFAULT_MODEL.to_string());
         let mut board = Board::new();
-        let mut tree = Tree::new(NeuralNetwork::new("frozen_model.pb"));
+        let mut tree = Tree::new(NeuralNetwork::new(&model));
         let (prob, _) = tree.nn.evaluate(&board);
         b.iter(|| {
             board.clear();
@@ -0,0 +1,61 @@
+/// 投了の判断基準です。
+/// 手番ごとに勝率が閾値を下回った連続回数を数え、条件を満たした時に投了と判断します。
+#[derive(Clone, Debug)]
+pub struct ResignPolicy {
+    /// 勝率がこの値未満の時に投了を考えます。0の場合は投了しません。
+    pub threshold: f32,
+    /// 投了を考え始める手数です。
+    pub min_move_cnt: usize,
+    /// 投了に必要な、勝率が閾値を下回った連続回数です。
+    pub consecutive: usize,
+    below_cnt: [usize; 2], // 手番(手数の偶奇)ごとの連続回数
+}
+
+impl Default for ResignPolicy {
+    fn default() -> Self {
+        Self::new(0.1, 0, 1)
+    }
+}
+
+impl ResignPolicy {
+    pub fn new(threshold: f32, min_move_cnt: usize, consecutive: usize) -> Self {
+        Self {
+            threshold: threshold,
+            min_move_cnt: min_move_cnt,
+            consecutive: consecutive,
+            below_cnt: [0, 0],
+        }
+    }
+
+    /// 連続回数をリセットします。対局の開始時に呼び出します。
+    pub fn clear(&mut self) {
+        self.below_cnt = [0, 0];
+    }
+
+    /// 手数move_cntの局面で着手する手番の勝率win_rateを記録し、投了すべきかどうかを返します。
+    pub fn should_resign(&mut self, move_cnt: usize, win_rate: f32) -> bool {
+        let cnt = &mut self.below_cnt[move_cnt % 2];
+        if win_rate < self.threshold {
+            *cnt += 1;
+        } else {
+            *cnt = 0;
+        }
+        move_cnt >= self.min_move_cnt && *cnt >= self.consecutive.max(1)
+    }
+}
+
+#[test]
+fn test_should_resign() {
+    let mut policy = ResignPolicy::new(0.1, 4, 2);
+    assert!(!policy.should_resign(0, 0.05));
+    assert!(!policy.should_resign(2, 0.05)); // 手数が足りない
+    assert!(policy.should_resign(4, 0.05));
+    assert!(!policy.should_resign(5, 0.05)); // 相手の手番は別に数える
+    assert!(!policy.should_resign(6, 0.5));
+    assert!(!policy.should_resign(8, 0.05));
+    policy.clear();
+    assert_eq!(policy.below_cnt, [0, 0]);
+    assert!(!policy.should_resign(10, 0.05)); // リセットしたので連続回数は1から数え直す
+    assert!(policy.should_resign(12, 0.05));
+    assert!(!ResignPolicy::new(0.0, 0, 1).should_resign(10, 0.0));
+}
@@ -0,0 +1,109 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use coord_convert::ev2str;
+use board::Board;
+use search::{Evaluate, Tree};
+use sgf_writer::{to_annotated_sgf, Annotation};
+use gtp::read_file;
+use convert::{main_line, parse_sgf};
+
+/// 変化として出力する読み筋の最大の長さです。
+const MAX_PV_LEN: usize = 10;
+
+/// 1局面の探索結果です。
+struct PositionReview {
+    win_rate: f32, // 着手する手番から見た勝率
+    best: usize,
+    pv: Vec<usize>,
+}
+
+/// 本譜movesの各局面と終局後の局面を探索します。
+fn search_positions<T: Evaluate>(
+    tree: &mut Tree<T>,
+    moves: &[usize],
+) -> Result<Vec<PositionReview>, String> {
+    let mut b = Board::new();
+    let mut reviews = Vec::with_capacity(moves.len() + 1);
+    for i in 0..moves.len() + 1 {
+        info!(target: "review", "reviewing move {}/{}", i + 1, moves.len() + 1);
+        let (best, win_rate) = tree.search(&b, 0.0, false, false);
+        reviews.push(PositionReview {
+            win_rate: win_rate,
+            best: best,
+            pv: tree.principal_variation(best, MAX_PV_LEN),
+        });
+        if let Some(&mov) = moves.get(i) {
+            if b.play(mov, false).is_err() {
+                return Err(format!("illegal move at move {}", i + 1));
+            }
+        }
+    }
+    Ok(reviews)
+}
+
+/// 探索結果reviewsから本譜movesの各着手の注釈を作成します。
+/// 着手した手番の勝率が最善手よりthreshold以上下がった着手に悪手の印をつけます。
+fn annotate(moves: &[usize], reviews: &[PositionReview], threshold: f32) -> Vec<Annotation> {
+    moves
+        .iter()
+        .enumerate()
+        .map(|(i, &mov)| {
+            let before = &reviews[i];
+            let after = 1.0 - reviews[i + 1].win_rate; // 着手した手番から見た勝率
+            let black_win_rate = if i % 2 == 0 { after } else { 1.0 - after };
+            let drop = before.win_rate - after;
+            let mut comment = format!("B win rate: {:.1}%", black_win_rate * 100.0);
+            let mut variation = Vec::new();
+            if before.best != mov {
+                comment.push_str(&format!(
+                    "\nbest: {} ({:.1}%)",
+                    ev2str(before.best),
+                    before.win_rate * 100.0
+                ));
+                variation = before.pv.clone();
+            }
+            let bad_move = drop >= threshold;
+            if bad_move {
+                comment.push_str(&format!("\nwin rate drop: {:.1}%", drop * 100.0));
+            }
+            Annotation {
+                comment: comment,
+                bad_move: bad_move,
+                variation: variation,
+            }
+        })
+        .collect()
+}
+
+/// SGFファイルinputの本譜をtreeで検討し、勝率、最善手と読み筋、悪手の印をつけたSGFをoutputに書き出します。
+pub fn review<T: Evaluate>(
+    tree: &mut Tree<T>,
+    input: &Path,
+    output: &Path,
+    threshold: f32,
+) -> Result<(), String> {
+    let sgf = read_file(&input.to_string_lossy()).map_err(|e| e.to_string())?;
+    let collection = parse_sgf(&sgf)?;
+    let moves = main_line(&collection)?;
+    let root = &collection[0];
+    let black = root.get_text("PB").unwrap_or(String::new());
+    let white = root.get_text("PW").unwrap_or(String::new());
+    let result = root.get_text("RE").unwrap_or(String::new());
+
+    let reviews = search_positions(tree, &moves)?;
+    let annotations = annotate(&moves, &reviews, threshold);
+    let annotated = to_annotated_sgf(&moves, &black, &white, &result, &annotations);
+    File::create(output)
+        .and_then(|mut file| file.write_all(annotated.as_bytes()))
+        .map_err(|e| e.to_string())?;
+
+    let bad_moves: Vec<String> = annotations
+        .iter()
+        .enumerate()
+        .filter(|&(_, a)| a.bad_move)
+        .map(|(i, _)| format!("{}({})", i + 1, ev2str(moves[i])))
+        .collect();
+    eprintln!("mistakes: {}", bad_moves.join(" "));
+    Ok(())
+}
@@ -1,15 +1,20 @@
 use board::*;
 use constants::*;
 use coord_convert::*;
+use intersection::Color;
 use numpy as np;
+use rand::XorShiftRng;
+use search_config::SearchConfig;
 use std::collections::HashMap;
+use std::f32;
+use time_control::{Overtime, TimeManager};
+use utils::{entropy_rng, seeded_rng};
 #[cfg(feature = "ponder")]
 use std::sync::atomic::{AtomicBool, Ordering};
 #[cfg(not(target_arch = "wasm32"))]
 use std::time;
 
 const MAX_NODE_CNT: usize = 16384; // 2 ^ 14
-const EXPAND_CNT: usize = 8;
 
 #[cfg(not(target_arch = "wasm32"))]
 fn duration2float(d: time::Duration) -> f32 {
@@ -18,6 +23,11 @@ fn duration2float(d: time::Duration) -> f32 {
 
 pub trait Evaluate {
     fn evaluate(&mut self, board: &Board) -> (Vec<f32>, Vec<f32>);
+
+    /// 探索の出力に添える、キャッシュのヒット率などの情報を返します。
+    fn info(&self) -> Option<String> {
+        None
+    }
 }
 
 // TODO - ponderは用意だけでまだ未実装。
@@ -26,51 +36,106 @@ lazy_static! {
     static ref TREE_STOP: AtomicBool = AtomicBool::new(false);
 }
 
-/// UCB1のCp関連の係数？
-static mut TREE_CP: f32 = 2.0;
-
 /// MCTSを実行するワーカー構造体です。
 pub struct Tree<T: Evaluate> {
-    main_time: f32,
-    byoyomi: f32,
-    left_time: f32,
+    time: TimeManager,
     node: Box<[Node; MAX_NODE_CNT]>,
     node_cnt: usize,
     pub root_id: usize, // ベンチマークのためにpubに
     root_move_cnt: usize,
     node_hashs: HashMap<u64, usize>,
     eval_cnt: usize,
+    max_playout: usize,
+    playout_cnt: usize,
+    rng: XorShiftRng,
+    config: SearchConfig,
+    cp: f32, // 現局面で使うPUCTの探索係数
     pub nn: T,
 }
 
 impl<T: Evaluate> Tree<T> {
     pub fn new(nn: T) -> Self {
+        Self::with_rng(nn, entropy_rng())
+    }
+
+    /// 乱数生成器をシードseedで初期化したTreeを作成します。
+    /// OSの乱数を使わないので、wasmではこちらを使います。
+    pub fn with_seed(nn: T, seed: u64) -> Self {
+        Self::with_rng(nn, seeded_rng(seed))
+    }
+
+    fn with_rng(nn: T, rng: XorShiftRng) -> Self {
         Self {
-            main_time: 0.0,
-            byoyomi: 1.0,
-            left_time: 0.0,
+            time: TimeManager::new(),
             node: box [Node::new(); MAX_NODE_CNT],
             node_cnt: 0,
             root_id: 0,
             root_move_cnt: 0,
             node_hashs: HashMap::new(),
             eval_cnt: 0,
+            max_playout: 0,
+            playout_cnt: 0,
+            rng: rng,
+            config: SearchConfig::default(),
+            cp: 0.0,
             nn: nn,
         }
     }
 
+    /// 持ち時間main_timeと1手ごとの秒読みbyoyomiを設定します。
     pub fn set_time(&mut self, main_time: f32, byoyomi: f32) {
-        self.main_time = main_time;
-        self.left_time = main_time;
-        self.byoyomi = byoyomi;
+        self.time.set_time_settings(
+            main_time,
+            Overtime::Byoyomi {
+                time: byoyomi,
+                periods: 1,
+            },
+        );
+    }
+
+    pub fn time_manager(&self) -> &TimeManager {
+        &self.time
+    }
+
+    pub fn time_manager_mut(&mut self) -> &mut TimeManager {
+        &mut self.time
+    }
+
+    /// 1手あたりのプレイアウト数の上限を設定します。0の場合は時間で探索を打ち切ります。
+    pub fn set_max_playout(&mut self, max_playout: usize) {
+        self.max_playout = max_playout;
+    }
+
+    /// 直前の探索で実行したプレイアウト数を返します。
+    pub fn playout_cnt(&self) -> usize {
+        self.playout_cnt
     }
 
-    pub fn set_left_time(&mut self, left_time: f32) {
-        self.left_time = left_time;
+    /// 乱数生成器をシードseedで初期化します。
+    /// プレイアウト数を固定したシングルスレッドの探索では、同じシードと同じモデルから同じ棋譜が得られます。
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = seeded_rng(seed);
+    }
+
+    pub fn set_config(&mut self, config: SearchConfig) {
+        self.config = config;
+    }
+
+    pub fn config(&self) -> &SearchConfig {
+        &self.config
+    }
+
+    pub fn config_mut(&mut self) -> &mut SearchConfig {
+        &mut self.config
+    }
+
+    /// 探索で使う乱数生成器を返します。
+    pub fn rng(&mut self) -> &mut XorShiftRng {
+        &mut self.rng
     }
 
     pub fn clear(&mut self) {
-        self.left_time = self.main_time;
+        self.time.reset();
         for nd in self.node.iter_mut() {
             nd.clear();
         }
@@ -135,6 +200,7 @@ impl<T: Evaluate> Tree<T> {
         for &rv in &np::argsort(prob, true) {
             if candidates.list.contains(&rv) {
                 nd.mov[nd.branch_cnt] = rv2ev(rv);
+                nd.prior[nd.branch_cnt] = prob[rv];
                 nd.prob[nd.branch_cnt] = prob[rv];
                 nd.branch_cnt += 1;
             }
@@ -156,26 +222,47 @@ impl<T: Evaluate> Tree<T> {
         } else {
             nd.total_value / nd.total_cnt as f32
         };
-        let cpsv = unsafe { TREE_CP } * (nd.total_cnt as f32).sqrt();
+        let fpu = if node_id == self.root_id {
+            self.config.fpu_root
+        } else {
+            self.config.fpu
+        };
+        let visited_prob = nd.prob
+            .iter()
+            .zip(nd.visit_cnt.iter())
+            .take(nd.branch_cnt)
+            .filter(|&(_, &c)| c > 0)
+            .map(|(&p, _)| p)
+            .sum();
+        let fpu_value = fpu.value(nd_action_value, visited_prob);
+        let cpsv = self.cp * (nd.total_cnt as f32).sqrt();
         let action_values = nd.value_win
             .iter()
             .zip(nd.visit_cnt.iter())
             .map(|(&w, &c)| {
                 if c == 0 {
-                    nd_action_value
+                    fpu_value
                 } else {
                     w / c as f32
                 }
             });
-        let upper_confidence_bounds =
-            multizip((action_values, nd.prob.iter(), nd.visit_cnt.iter()))
-                .map(|(r, &p, &c)| r + cpsv * p / (c + 1) as f32)
-                .take(nd.branch_cnt);
+        let upper_confidence_bounds = multizip((
+            action_values,
+            nd.prob.iter(),
+            nd.visit_cnt.iter(),
+            nd.proof.iter(),
+        )).map(|(r, &p, &c, &proof)| match proof {
+            // 勝ちが確定した着手は必ず選び、負けが確定した着手は選ばない
+            Proof::Win => f32::INFINITY,
+            Proof::Loss => f32::NEG_INFINITY,
+            _ => r + cpsv * p / (c + 1) as f32,
+        })
+            .take(nd.branch_cnt);
         let best = np::argmax(upper_confidence_bounds);
         let next_id = nd.next_id[best];
         let next_move = nd.mov[best];
         let is_head_node = !self.has_next(node_id, best, b.get_move_cnt() + 1)
-            || nd.visit_cnt[best] < EXPAND_CNT
+            || nd.visit_cnt[best] < self.config.expand_cnt
             || b.get_move_cnt() > BVCNT * 2
             || (next_move == PASS && b.get_prev_move() == PASS);
 
@@ -221,29 +308,58 @@ impl<T: Evaluate> Tree<T> {
             self.best_by_upper_confidence_bound(b, node_id);
         route.push((node_id, best));
 
+        // 手数の上限は終局ではないので、is_head_nodeとして評価値を使う
+        let is_terminal = next_move == PASS && b.get_prev_move() == PASS;
+        let player = b.get_turn();
         let _ = b.play(next_move, false);
 
-        let value = if is_head_node {
+        let proof = self.node[node_id].proof[best];
+        let value = if proof != Proof::Unknown {
+            proof.value()
+        } else if is_terminal {
+            // 終局図は面積で数えて勝敗を確定させる
+            let score = b.area_score();
+            let proof = Proof::from_score(if player == Color::Black {
+                score
+            } else {
+                -score
+            });
+            let nd = &mut self.node[node_id];
+            nd.proof[best] = proof;
+            nd.value[best] = proof.value();
+            nd.evaluated[best] = true;
+            proof.value()
+        } else if is_head_node {
             if self.node[node_id].evaluated[best] {
                 self.node[node_id].value[best]
             } else {
                 self.evaluate_child_node(b, node_id, best)
             }
         } else {
-            -self.search_branch(b, next_id, route)
+            let value = -self.search_branch(b, next_id, route);
+            // 子ノードの勝敗が確定したら、この着手の勝敗も確定する
+            match self.node[next_id].proof() {
+                Proof::Unknown => value,
+                child_proof => {
+                    let proof = child_proof.opposite();
+                    self.node[node_id].proof[best] = proof;
+                    proof.value()
+                }
+            }
         };
 
         let nd = &mut self.node[node_id];
         nd.total_value += value;
         nd.total_cnt += 1;
         nd.value_win[best] += value;
+        nd.value_sq[best] += value * value;
         nd.visit_cnt[best] += 1;
 
         value
     }
 
-    fn keep_playout<F: Fn(usize) -> bool>(&mut self, b: &Board, exit_condition: F) {
-        let mut search_idx = 1;
+    fn keep_playout<F: Fn(&Self, usize) -> bool>(&mut self, b: &Board, exit_condition: F) {
+        let mut search_idx = 0; // 実行したプレイアウト数
         self.eval_cnt = 0;
         let mut b_cpy = Board::new();
         loop {
@@ -251,18 +367,18 @@ impl<T: Evaluate> Tree<T> {
             let root_id = self.root_id; // 下の行でownershipを解決するための変数
             self.search_branch(&mut b_cpy, root_id, &mut Vec::new());
             search_idx += 1;
+            self.playout_cnt += 1;
+            // 時間の確認などで重い条件は、exit_conditionの側で間引く
             #[cfg(feature = "ponder")]
             {
-                if search_idx % 64 == 0
-                    && (ponder && TREE_STOP.load(Ordering::Relaxed) || exit_condition(search_idx))
-                {
+                if ponder && TREE_STOP.load(Ordering::Relaxed) || exit_condition(self, search_idx) {
                     TREE_STOP.store(false, Ordering::Relaxed);
                     break;
                 }
             }
             #[cfg(not(feature = "ponder"))]
             {
-                if search_idx % 64 == 0 && exit_condition(search_idx) {
+                if exit_condition(self, search_idx) {
                     break;
                 }
             }
@@ -283,20 +399,73 @@ impl<T: Evaluate> Tree<T> {
         let (prob, _) = self.nn.evaluate(b);
         self.root_id = self.create_node(b, &prob);
         self.root_move_cnt = b.get_move_cnt();
-        unsafe {
-            TREE_CP = if self.root_move_cnt < 8 { 0.01 } else { 1.5 };
+        self.playout_cnt = 0;
+        self.cp = self.config.c_puct_at(self.root_move_cnt);
+        if self.config.noise_epsilon > 0.0 {
+            self.add_dirichlet_noise();
         }
     }
 
-    fn get_search_time(&self) -> f32 {
-        if self.main_time == 0.0 || self.left_time < self.byoyomi * 2.0 {
-            self.byoyomi.max(1.0)
-        } else {
-            self.left_time / (55.0 + (50 - self.root_move_cnt).max(0) as f32)
+    /// ルートノードの事前確率にディリクレノイズを混ぜます。
+    /// 常にネットワークの事前確率に混ぜるので、同じノードが再びルートになってもノイズは蓄積しません。
+    fn add_dirichlet_noise(&mut self) {
+        use rand::distributions::{Gamma, IndependentSample};
+
+        let gamma = Gamma::new(self.config.noise_alpha as f64, 1.0);
+        let epsilon = self.config.noise_epsilon;
+        let rng = &mut self.rng;
+        let nd = &mut self.node[self.root_id];
+        let noise: Vec<f32> = (0..nd.branch_cnt)
+            .map(|_| gamma.ind_sample(rng) as f32)
+            .collect();
+        let noise_sum: f32 = noise.iter().sum();
+        if noise_sum <= 0.0 {
+            return;
+        }
+        for ((p, &prior), n) in nd.prob.iter_mut().zip(nd.prior.iter()).zip(noise.into_iter()) {
+            *p = (1.0 - epsilon) * prior + epsilon * n / noise_sum;
         }
     }
 
-    fn _search<F: Fn(usize) -> bool>(
+    /// 残りremainingプレイアウトで次善手の訪問回数が最善手に追いつく可能性があるか判断します。
+    fn can_best_change(&self, remaining: usize) -> bool {
+        let nd = &self.node[self.root_id];
+        let (best, second) = nd.best2();
+        nd.visit_cnt[best] <= nd.visit_cnt[second] + remaining
+    }
+
+    /// time_秒を割り当て、最善手が不安定な間はmax_time秒まで延長する探索で、
+    /// elapsed秒でsearch_idx回プレイアウトした時点で打ち切るかどうかを返します。
+    #[cfg(not(target_arch = "wasm32"))]
+    fn is_time_up(
+        &self,
+        search_idx: usize,
+        elapsed: f32,
+        time_: f32,
+        max_time: f32,
+        early_stop: bool,
+    ) -> bool {
+        // 最善手が不安定な間はmax_timeまで延長するので、残り時間もmax_timeまでで見積もる
+        let limit = if self.is_best_unstable() {
+            max_time
+        } else {
+            time_
+        };
+        // これまでの探索速度から見積もった残りのプレイアウト数
+        let speed = search_idx as f32 / elapsed.max(0.001);
+        let remaining = (speed * (limit - elapsed).max(0.0)) as usize;
+        elapsed > limit || (early_stop && !self.can_best_change(remaining))
+    }
+
+    /// 最善手が入れ替わる可能性があり、探索を延長すべきか判断します。
+    fn is_best_unstable(&self) -> bool {
+        let nd = &self.node[self.root_id];
+        let (best, second) = nd.best2();
+        nd.visit_cnt[best] < nd.visit_cnt[second] * 3 / 2
+            || self.branch_rate(nd, second) > self.branch_rate(nd, best)
+    }
+
+    fn _search<F: Fn(&Self, usize) -> bool>(
         &mut self,
         b: &Board,
         ponder: bool,
@@ -310,8 +479,37 @@ impl<T: Evaluate> Tree<T> {
             best = best2.0;
             second = best2.1;
         }
+        if self.root_move_cnt < self.config.temperature_moves && self.config.temperature > 0.0 {
+            let temperature = self.config.temperature;
+            if let Some(sampled) = self.sample_by_visit_cnt(temperature) {
+                // 選んだ着手以外で最も訪問回数の多い着手は、元の最善手
+                if sampled != best {
+                    second = best;
+                    best = sampled;
+                }
+            }
+        } else if self.config.lcb {
+            let nd = &self.node[self.root_id];
+            if let Some(lcb_best) = nd.best_lcb(self.config.lcb_z, self.config.lcb_min_visit_ratio)
+            {
+                if lcb_best != best {
+                    second = best;
+                    best = lcb_best;
+                }
+            }
+        }
 
         let nd = &self.node[self.root_id];
+        if let Some(win) = (0..nd.branch_cnt).find(|&i| nd.proof[i] == Proof::Win) {
+            best = win;
+        } else if nd.proof[best] == Proof::Loss {
+            if let Some(alt) = np::argsort(&nd.visit_cnt[0..nd.branch_cnt], true)
+                .into_iter()
+                .find(|&i| nd.proof[i] != Proof::Loss)
+            {
+                best = alt;
+            }
+        }
         let mut next_move = nd.mov[best];
         let mut win_rate = self.branch_rate(&nd, best);
 
@@ -322,6 +520,30 @@ impl<T: Evaluate> Tree<T> {
         (next_move, win_rate)
     }
 
+    /// ルートノードの子ノードを訪問回数のtemperature分の1乗に比例する確率で選びます。
+    /// 訪問済みの子ノードがない場合はNoneを返します。
+    fn sample_by_visit_cnt(&mut self, temperature: f32) -> Option<usize> {
+        use rand::Rng;
+
+        let nd = &self.node[self.root_id];
+        let weights: Vec<f64> = nd.visit_cnt[0..nd.branch_cnt]
+            .iter()
+            .map(|&c| (c as f64).powf(1.0 / temperature as f64))
+            .collect();
+        let sum: f64 = weights.iter().sum();
+        if sum <= 0.0 {
+            return None;
+        }
+        let mut r = self.rng.gen::<f64>() * sum;
+        for (i, &w) in weights.iter().enumerate() {
+            if r < w {
+                return Some(i);
+            }
+            r -= w;
+        }
+        weights.iter().rposition(|&w| w > 0.0)
+    }
+
     /// time_で決定される時間の間、MCTSを実行し、最も勝率の高い着手と勝率を返します。
     #[cfg(not(target_arch = "wasm32"))]
     pub fn search(&mut self, b: &Board, time: f32, ponder: bool, clean: bool) -> (usize, f32) {
@@ -329,31 +551,55 @@ impl<T: Evaluate> Tree<T> {
         self.pre_search(b);
 
         if self.node[self.root_id].branch_cnt <= 1 {
-            eprintln!("\nmove count={}:", self.root_move_cnt + 1);
+            info!(target: "search", "\nmove count={}:", self.root_move_cnt + 1);
             self.print_info(self.root_id);
             return (PASS, 0.5);
         }
 
         self.delete_node();
 
-        let time_ = if time == 0.0 {
-            self.get_search_time()
+        let (time_, max_time) = if time == 0.0 {
+            (
+                self.time.allocate(self.root_move_cnt),
+                self.time.max_time(self.root_move_cnt),
+            )
         } else {
-            time
+            (time, time)
         };
-        let (next_move, win_rate) = self._search(b, ponder, clean, |_| {
-            duration2float(start.elapsed().unwrap()) > time_
+        let max_playout = self.max_playout;
+        // 温度で着手を選ぶ時は訪問回数の分布自体が必要なので打ち切らない
+        let early_stop = self.config.early_stop
+            && !(self.root_move_cnt < self.config.temperature_moves
+                && self.config.temperature > 0.0);
+        let (next_move, win_rate) = self._search(b, ponder, clean, |tree, search_idx| {
+            if max_playout > 0 {
+                search_idx >= max_playout
+                    || (early_stop && !tree.can_best_change(max_playout - search_idx))
+            } else if search_idx % 64 != 0 {
+                false
+            } else {
+                let elapsed = duration2float(start.elapsed().unwrap());
+                tree.is_time_up(search_idx, elapsed, time_, max_time, early_stop)
+            }
         });
 
         if !ponder {
-            eprintln!(
+            info!(
+                target: "search",
                 "\nmove count={}: left time={:.1}[sec] evaluated={}",
                 self.root_move_cnt + 1,
-                (self.left_time - time).max(0.0), // stand_outやalmost_winの時にずれるけれども、目をつぶる。先にleft_timeを計算すればいいがそうすると、printの時間が経過時間に含まれない。
+                self.time.left_time(), // この手の消費時間を引く前の値。先に引くとprintの時間が経過時間に含まれない。
                 self.eval_cnt
             );
+            if let Some(info) = self.nn.info() {
+                info!(target: "search", "{}", info);
+            }
             self.print_info(self.root_id);
-            self.left_time = (self.left_time - duration2float(start.elapsed().unwrap())).max(0.0);
+            let elapsed = duration2float(start.elapsed().unwrap());
+            self.time.consume(elapsed);
+            if time == 0.0 {
+                self.time.save(time_, elapsed);
+            }
         }
 
         (next_move, win_rate)
@@ -373,7 +619,7 @@ impl<T: Evaluate> Tree<T> {
         self.pre_search(b);
 
         if self.node[self.root_id].branch_cnt <= 1 {
-            eprintln!("\nmove count={}:", self.root_move_cnt + 1);
+            info!(target: "search", "\nmove count={}:", self.root_move_cnt + 1);
             self.print_info(self.root_id);
             return (PASS, 0.5);
         }
@@ -381,16 +627,20 @@ impl<T: Evaluate> Tree<T> {
         self.delete_node();
 
         let (next_move, win_rate) =
-            self._search(b, ponder, clean, |search_idx| search_idx > max_playout);
+            self._search(b, ponder, clean, |_, search_idx| search_idx >= max_playout);
 
         if !ponder {
-            eprintln!(
+            info!(
+                target: "search",
                 "\nmove count={}: evaluated={}",
                 self.root_move_cnt + 1,
                 self.eval_cnt
             );
+            if let Some(info) = self.nn.info() {
+                info!(target: "search", "{}", info);
+            }
             self.print_info(self.root_id);
-            self.left_time = (self.left_time - duration2float(start.elapsed().unwrap())).max(0.0);
+            self.time.consume(duration2float(start.elapsed().unwrap()));
         }
 
         (next_move, win_rate)
@@ -407,12 +657,14 @@ impl<T: Evaluate> Tree<T> {
         nd.value_win[id] / nd.visit_cnt[id].max(1) as f32 / 2.0 + 0.5
     }
 
-    fn best_sequence(&self, node_id: usize, head_move: usize) -> String {
+    /// ノードnode_idから訪問回数の最も多い着手を辿った手順を最大max_len手返します。
+    /// head_moveはノードnode_idに至った着手です。
+    fn sequence(&self, node_id: usize, head_move: usize, max_len: usize) -> Vec<usize> {
         let mut node_id = node_id;
-        let mut seq_str = format!("{:>3}", ev2str(head_move));
+        let mut seq = Vec::new();
         let mut next_move = head_move;
 
-        for _ in 0..7 {
+        while seq.len() < max_len {
             let nd = &self.node[node_id];
             if next_move == PASS || nd.branch_cnt < 1 {
                 break;
@@ -423,7 +675,7 @@ impl<T: Evaluate> Tree<T> {
                 break;
             }
             next_move = nd.mov[best];
-            seq_str = format!("{}->{:>3}", seq_str, ev2str(next_move));
+            seq.push(next_move);
 
             if !self.has_next(node_id, best, nd.move_cnt + 1) {
                 break;
@@ -431,13 +683,67 @@ impl<T: Evaluate> Tree<T> {
             node_id = nd.next_id[best];
         }
 
+        seq
+    }
+
+    fn best_sequence(&self, node_id: usize, head_move: usize) -> String {
+        let mut seq_str = format!("{:>3}", ev2str(head_move));
+        for &mov in &self.sequence(node_id, head_move, 7) {
+            seq_str = format!("{}->{:>3}", seq_str, ev2str(mov));
+        }
         seq_str
     }
 
+    /// 直前の探索のルートノードで着手movから始まる読み筋を最大max_len手返します。
+    /// movがルートノードの候補手でなければ空です。
+    pub fn principal_variation(&self, mov: usize, max_len: usize) -> Vec<usize> {
+        let nd = &self.node[self.root_id];
+        let br_id = match (0..nd.branch_cnt).find(|&i| nd.mov[i] == mov) {
+            Some(i) => i,
+            None => return Vec::new(),
+        };
+        let mut pv = vec![mov];
+        if max_len > 1 && self.has_next(self.root_id, br_id, nd.move_cnt + 1) {
+            pv.extend(self.sequence(nd.next_id[br_id], mov, max_len - 1));
+        }
+        pv.truncate(max_len);
+        pv
+    }
+
+    /// 直前の探索のルートノードの訪問回数の分布を返します。
+    /// 碁盤の線形座標の順で、最後がパスです。訪問がなければNoneを返します。
+    pub fn root_policy(&self) -> Option<Vec<f32>> {
+        let nd = &self.node[self.root_id];
+        let total: usize = nd.visit_cnt[0..nd.branch_cnt].iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let mut policy = vec![0.0; BVCNT + 1];
+        for i in 0..nd.branch_cnt {
+            policy[ev2rv(nd.mov[i])] = nd.visit_cnt[i] as f32 / total as f32;
+        }
+        Some(policy)
+    }
+
+    /// 直前の探索のルートノードの訪問した候補手の統計を訪問回数の多い順に返します。
+    pub fn root_moves(&self) -> Vec<MoveStats> {
+        let nd = &self.node[self.root_id];
+        np::argsort(&nd.visit_cnt[0..nd.branch_cnt], true)
+            .into_iter()
+            .filter(|&i| nd.visit_cnt[i] > 0)
+            .map(|i| MoveStats {
+                mov: nd.mov[i],
+                visit_cnt: nd.visit_cnt[i],
+                win_rate: self.branch_rate(nd, i),
+                prob: nd.prob[i],
+            })
+            .collect()
+    }
+
     pub fn print_info(&self, node_id: usize) {
         let nd = &self.node[node_id];
         let order_ = np::argsort(&nd.visit_cnt[0..nd.branch_cnt], true);
-        eprintln!("|move|count  |rate |value|prob | best sequence");
+        info!(target: "search", "|move|count  |rate |value|prob | best sequence");
         for i in 0..order_.len().min(9) {
             let m = order_[i];
             let visit_cnt = nd.visit_cnt[m];
@@ -452,7 +758,8 @@ impl<T: Evaluate> Tree<T> {
             };
             let value = (nd.value[m] / 2.0 + 0.5) * 100.0;
 
-            eprintln!(
+            info!(
+                target: "search",
                 "|{:>4}|{:7}|{:5.1}|{:5.1}|{:5.1}| {}",
                 ev2str(nd.mov[m]),
                 visit_cnt,
@@ -465,18 +772,72 @@ impl<T: Evaluate> Tree<T> {
     }
 }
 
+/// ルートノードの候補手の探索結果です。
+#[derive(Clone, Debug)]
+pub struct MoveStats {
+    pub mov: usize,
+    pub visit_cnt: usize,
+    /// 着手する手番から見た勝率です。
+    pub win_rate: f32,
+    /// ポリシーの値です。
+    pub prob: f32,
+}
+
+/// 探索で確定した勝敗です。
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Proof {
+    Unknown,
+    Win,
+    Loss,
+    Draw,
+}
+
+impl Proof {
+    /// 着手した手番から見たスコアscoreから勝敗を返します。
+    fn from_score(score: f32) -> Self {
+        if score > 0.0 {
+            Proof::Win
+        } else if score < 0.0 {
+            Proof::Loss
+        } else {
+            Proof::Draw
+        }
+    }
+
+    /// 相手から見た勝敗を返します。
+    fn opposite(&self) -> Self {
+        match *self {
+            Proof::Win => Proof::Loss,
+            Proof::Loss => Proof::Win,
+            p => p,
+        }
+    }
+
+    /// 勝敗に対応するバリューを返します。
+    fn value(&self) -> f32 {
+        match *self {
+            Proof::Win => 1.0,
+            Proof::Loss => -1.0,
+            _ => 0.0,
+        }
+    }
+}
+
 /// MCTSの各ノードです。
 #[derive(Clone, Copy)] // 配列の初期化で楽するためにCopyにした
 struct Node {
     // 各配列のBVCNT番目の要素はPASSに対応する着手
     mov: [usize; BVCNT + 1],
-    prob: [f32; BVCNT + 1],
+    prior: [f32; BVCNT + 1], // ネットワークの事前確率
+    prob: [f32; BVCNT + 1],  // 探索で使う事前確率。ルートではノイズを混ぜたもの
     value: [f32; BVCNT + 1],
     value_win: [f32; BVCNT + 1],
+    value_sq: [f32; BVCNT + 1], // 分散の計算のための価値の2乗の和
     visit_cnt: [usize; BVCNT + 1],
     next_id: [usize; BVCNT + 1],
     next_hash: [u64; BVCNT + 1],
     evaluated: [bool; BVCNT + 1],
+    proof: [Proof; BVCNT + 1], // 各着手の、このノードの手番から見た確定した勝敗
     branch_cnt: usize,
     total_value: f32,
     total_cnt: usize,
@@ -497,13 +858,16 @@ impl Node {
         use utils::fill;
 
         fill(&mut self.mov, VNULL);
+        fill(&mut self.prior, 0.0);
         fill(&mut self.prob, 0.0);
         fill(&mut self.value, 0.0);
         fill(&mut self.value_win, 0.0);
+        fill(&mut self.value_sq, 0.0);
         fill(&mut self.visit_cnt, 0);
         fill(&mut self.next_id, usize::max_value());
         fill(&mut self.next_hash, 0);
         fill(&mut self.evaluated, false);
+        fill(&mut self.proof, Proof::Unknown);
     }
 
     pub fn clear(&mut self) {
@@ -518,4 +882,343 @@ impl Node {
         let order_ = np::argsort(&self.visit_cnt[0..self.branch_cnt], true);
         (order_[0], order_[1])
     }
+
+    /// このノードの手番から見た確定した勝敗を返します。
+    /// 勝ちの着手が1つでもあれば勝ち、すべての着手の勝敗が確定していれば最善の結果です。
+    fn proof(&self) -> Proof {
+        let proofs = &self.proof[0..self.branch_cnt];
+        if proofs.iter().any(|&p| p == Proof::Win) {
+            Proof::Win
+        } else if proofs.is_empty() || proofs.iter().any(|&p| p == Proof::Unknown) {
+            Proof::Unknown
+        } else if proofs.iter().any(|&p| p == Proof::Draw) {
+            Proof::Draw
+        } else {
+            Proof::Loss
+        }
+    }
+
+    /// 子ノードidの価値の信頼下限を返します。zは標準偏差の何倍を引くかです。
+    /// 訪問回数が2未満の時は分散が計算できないので最小値を返します。
+    pub fn lower_confidence_bound(&self, id: usize, z: f32) -> f32 {
+        let n = self.visit_cnt[id];
+        if n < 2 {
+            return -1.0 - z;
+        }
+        let n = n as f32;
+        let mean = self.value_win[id] / n;
+        let variance = (self.value_sq[id] / n - mean * mean).max(0.0) * n / (n - 1.0);
+        mean - z * (variance / n).sqrt()
+    }
+
+    /// 最多訪問回数のmin_visit_ratio倍以上訪問された子ノードの中で、
+    /// 価値の信頼下限が最も高いものを返します。
+    pub fn best_lcb(&self, z: f32, min_visit_ratio: f32) -> Option<usize> {
+        let max_visit = self.visit_cnt[0..self.branch_cnt]
+            .iter()
+            .max()
+            .cloned()
+            .unwrap_or(0);
+        let min_visit = (max_visit as f32 * min_visit_ratio).max(2.0);
+        let candidates: Vec<usize> = (0..self.branch_cnt)
+            .filter(|&i| self.visit_cnt[i] as f32 >= min_visit)
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+        let best = np::argmax(
+            candidates
+                .iter()
+                .map(|&i| self.lower_confidence_bound(i, z)),
+        );
+        Some(candidates[best])
+    }
+}
+
+#[test]
+fn test_best_lcb() {
+    let mut nd = Node::new();
+    nd.branch_cnt = 2;
+    nd.visit_cnt[0] = 100;
+    nd.value_win[0] = 50.0;
+    nd.value_sq[0] = 100.0;
+    nd.visit_cnt[1] = 50;
+    nd.value_win[1] = 22.5;
+    nd.value_sq[1] = 10.125;
+    assert_eq!(nd.best2().0, 0);
+    assert_eq!(nd.best_lcb(1.96, 0.1), Some(1));
+    assert_eq!(nd.best_lcb(1.96, 0.6), Some(0));
+}
+
+#[test]
+fn test_node_proof() {
+    let mut nd = Node::new();
+    nd.branch_cnt = 2;
+    assert_eq!(nd.proof(), Proof::Unknown);
+    nd.proof[0] = Proof::Loss;
+    assert_eq!(nd.proof(), Proof::Unknown);
+    nd.proof[1] = Proof::Draw;
+    assert_eq!(nd.proof(), Proof::Draw);
+    nd.proof[1] = Proof::Loss;
+    assert_eq!(nd.proof(), Proof::Loss);
+    nd.proof[1] = Proof::Win;
+    assert_eq!(nd.proof().opposite(), Proof::Loss);
+}
+
+#[cfg(test)]
+fn test_tree<T: Evaluate>(nn: T, max_playout: usize) -> Tree<T> {
+    let mut tree = Tree::new(nn);
+    tree.set_max_playout(max_playout);
+    tree.config_mut().early_stop = false;
+    tree.set_seed(1);
+    tree
+}
+
+#[test]
+fn test_search_uniform() {
+    use evaluator::UniformEvaluator;
+
+    let mut tree = test_tree(UniformEvaluator, 200);
+    let (mov, win_rate) = tree.search(&Board::new(), 0.0, false, false);
+    let moves = tree.root_moves();
+    assert_eq!(moves[0].mov, mov);
+    assert_eq!(win_rate, 0.5);
+    // 事前確率もバリューも同じなので、訪問は多くの候補手に分散する
+    assert!(moves.len() > BVCNT / 2);
+    let total: usize = moves.iter().map(|m| m.visit_cnt).sum();
+    assert_eq!(total, 200);
+    assert_eq!(tree.playout_cnt(), 200);
+    let policy = tree.root_policy().unwrap();
+    assert!((policy.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+}
+
+#[test]
+fn test_search_prior_and_backup() {
+    use evaluator::ScriptedEvaluator;
+
+    let b = Board::new();
+
+    // 事前確率が高い着手が最も多く訪問される
+    let prior_move = xy2ev(3, 3);
+    let mut nn = ScriptedEvaluator::new();
+    let mut prob = vec![0.1 / BVCNT as f32; BVCNT + 1];
+    prob[ev2rv(prior_move)] = 0.9;
+    nn.set(&b, prob, 0.0);
+    let mut tree = test_tree(nn, 100);
+    let (mov, _) = tree.search(&b, 0.0, false, false);
+    assert_eq!(mov, prior_move);
+
+    // 相手から見て負けの局面になる着手が見つかり、勝率が伝播する
+    let good_move = xy2ev(5, 5);
+    let mut b1 = Board::new();
+    let _ = b1.play(good_move, false);
+    let mut nn = ScriptedEvaluator::new();
+    nn.set_value(&b1, -1.0);
+    let mut tree = test_tree(nn, 800);
+    let (mov, win_rate) = tree.search(&b, 0.0, false, false);
+    assert_eq!(mov, good_move);
+    assert!(win_rate > 0.5);
+    let moves = tree.root_moves();
+    assert_eq!(moves[0].mov, good_move);
+    assert!(moves[1].win_rate <= 0.5);
+}
+
+#[test]
+fn test_search_pass() {
+    use evaluator::UniformEvaluator;
+
+    // 黒が大きくリードしている局面
+    let diagram = "
+        . . . . X . . . .
+        . . . . X . . . .
+        . . . . X . . . .
+        . . . . X . . . .
+        . . . . X . . . .
+        . . . . X . . . .
+        . . . . X . . . .
+        . . . . X . . . .
+        O . . . X . . . .
+    ";
+    // 白がパスした後なら、黒はパスして勝ちを確定できる
+    let mut b = Board::from_diagram(diagram, Color::White).unwrap();
+    let _ = b.play(PASS, false);
+    let mut tree = test_tree(UniformEvaluator, 800);
+    let (mov, win_rate) = tree.search(&b, 0.0, false, false);
+    assert_eq!((mov, win_rate), (PASS, 1.0));
+
+    // 黒がパスした後でも、白はパスすると負けるのでパスしない
+    let mut b = Board::from_diagram(diagram, Color::Black).unwrap();
+    let _ = b.play(PASS, false);
+    tree.clear();
+    let (mov, _) = tree.search(&b, 0.0, false, false);
+    assert!(mov != PASS);
+}
+
+#[test]
+fn test_dirichlet_noise() {
+    use evaluator::UniformEvaluator;
+
+    let b = Board::new();
+    let mut tree = test_tree(UniformEvaluator, 1);
+    tree.config_mut().noise_epsilon = 0.25;
+    // 同じ局面が何度ルートになっても、ノイズはネットワークの事前確率に混ぜる
+    for _ in 0..20 {
+        tree.pre_search(&b);
+    }
+    let nd = &tree.node[tree.root_id];
+    for i in 0..nd.branch_cnt {
+        assert!(nd.prob[i] >= 0.75 * nd.prior[i]);
+    }
+    let sum: f32 = nd.prob[0..nd.branch_cnt].iter().sum();
+    assert!((sum - 1.0).abs() < 1e-4);
+}
+
+#[test]
+fn test_search_time_extension() {
+    use evaluator::ScriptedEvaluator;
+
+    // 2つの着手の事前確率が近く、最善手が不安定な局面
+    let b = Board::new();
+    let mut prob = vec![0.0; BVCNT + 1];
+    prob[ev2rv(xy2ev(3, 3))] = 0.46;
+    prob[ev2rv(xy2ev(7, 7))] = 0.44;
+    prob[BVCNT] = 0.1;
+    let mut nn = ScriptedEvaluator::new();
+    nn.set(&b, prob, 0.0);
+    let mut tree = test_tree(nn, 100);
+    let _ = tree.search(&b, 0.0, false, false);
+    assert!(tree.is_best_unstable());
+    // 割り当て時間1秒、延長の上限2秒で、1.5秒で100回プレイアウトした時点
+    // 割り当て時間を過ぎても、最善手が不安定な間は探索を続ける
+    assert!(!tree.is_time_up(100, 1.5, 1.0, 2.0, true));
+    assert!(!tree.is_time_up(100, 1.5, 1.0, 2.0, false));
+    // 延長の上限を過ぎたら打ち切る
+    assert!(tree.is_time_up(100, 2.1, 1.0, 2.0, true));
+
+    // 最善手が安定した局面では割り当て時間で打ち切る
+    let mut prob = vec![0.0; BVCNT + 1];
+    prob[ev2rv(xy2ev(3, 3))] = 0.9;
+    prob[BVCNT] = 0.1;
+    let mut nn = ScriptedEvaluator::new();
+    nn.set(&b, prob, 0.0);
+    let mut tree = test_tree(nn, 100);
+    let _ = tree.search(&b, 0.0, false, false);
+    assert!(!tree.is_best_unstable());
+    assert!(!tree.is_time_up(100, 0.5, 1.0, 2.0, false));
+    assert!(tree.is_time_up(100, 1.1, 1.0, 2.0, false));
+    // 残りの探索で最善手が入れ替わらなければ、割り当て時間の前でも打ち切る
+    assert!(tree.is_time_up(100, 0.9, 1.0, 2.0, true));
+}
+
+#[test]
+fn test_search_repeated() {
+    // パスの事前確率が高く、プレイアウトがよくパスで終わる評価器
+    struct PassFirst;
+
+    impl Evaluate for PassFirst {
+        fn evaluate(&mut self, _: &Board) -> (Vec<f32>, Vec<f32>) {
+            let mut prob = vec![0.5 / BVCNT as f32; BVCNT + 1];
+            prob[BVCNT] = 0.5;
+            (prob, vec![0.0])
+        }
+    }
+
+    // 黒が大きくリードしている局面で、直前の着手はパスではない
+    let diagram = "
+        . . . . X . . . .
+        . . . . X . . . .
+        . . . . X . . . .
+        . . . . X . . . .
+        . . . . X . . . .
+        . . . . X . . . .
+        . . . . X . . . .
+        . . . . X . . . .
+        O . . . X . . . .
+    ";
+    let b = Board::from_diagram(diagram, Color::Black).unwrap();
+    let mut tree = test_tree(PassFirst, 400);
+    // 前のプレイアウトがパスで終わっても、ルートでの黒のパスは終局ではない
+    for _ in 0..4 {
+        let _ = tree.search(&b, 0.0, false, false);
+        let nd = &tree.node[tree.root_id];
+        let pass = (0..nd.branch_cnt).find(|&i| nd.mov[i] == PASS).unwrap();
+        assert!(nd.visit_cnt[pass] > 0);
+        assert_eq!(nd.proof[pass], Proof::Unknown);
+    }
+}
+
+#[test]
+fn test_self_play_reproducible() {
+    use evaluator::RolloutEvaluator;
+
+    // 同じシードなら、ノイズや温度、ロールアウトがあっても同じ棋譜と勝者になる
+    let play = |seed: u64| {
+        let mut nn = RolloutEvaluator::new(1);
+        nn.set_seed(seed);
+        let mut tree = Tree::with_seed(nn, seed);
+        tree.set_max_playout(16);
+        tree.config_mut().noise_epsilon = 0.25;
+        tree.config_mut().temperature_moves = 10;
+        let mut b = Board::new();
+        while b.get_move_cnt() < BVCNT * 2 {
+            let prev_move = b.get_prev_move();
+            let (mov, _) = tree.search(&b, 0.0, false, false);
+            let _ = b.play(mov, false);
+            if prev_move == PASS && mov == PASS {
+                break;
+            }
+        }
+        let score = b.final_score(&mut seeded_rng(seed));
+        (b.get_history().to_vec(), score > 0.0)
+    };
+    assert!(play(3) == play(3));
+}
+
+#[test]
+fn test_search_clean_with_temperature() {
+    // 事前確率がある着手とパスに集中し、どの局面も黒の勝ちと評価する評価器
+    struct TwoMoves;
+
+    impl Evaluate for TwoMoves {
+        fn evaluate(&mut self, b: &Board) -> (Vec<f32>, Vec<f32>) {
+            let mut prob = vec![0.0; BVCNT + 1];
+            prob[ev2rv(xy2ev(7, 5))] = 0.6;
+            prob[BVCNT] = 0.4;
+            let value = if b.get_turn() == Color::Black { 0.5 } else { -0.5 };
+            (prob, vec![value])
+        }
+    }
+
+    let diagram = "
+        . . . . X . . . .
+        . . . . X . . . .
+        . . . . X . . . .
+        . . . . X . . . .
+        . . . . X . . . .
+        . . . . X . . . .
+        . . . . X . . . .
+        . . . . X . . . .
+        O . . . X . . . .
+    ";
+    let b = Board::from_diagram(diagram, Color::Black).unwrap();
+    let search = |seed: u64, clean: bool| {
+        let mut tree = test_tree(TwoMoves, 100);
+        tree.set_seed(seed);
+        tree.config_mut().temperature_moves = 1;
+        let (mov, _) = tree.search(&b, 0.0, false, clean);
+        let nd = &tree.node[tree.root_id];
+        let (best, second) = nd.best2();
+        assert!(nd.mov[best] == xy2ev(7, 5) && nd.mov[second] == PASS);
+        assert!(nd.value_win[best] * nd.value_win[second] > 0.0);
+        mov
+    };
+    // 温度でパスを選んでも、cleanの時は最も訪問回数の多い着手に置き換える
+    let mut pass_cnt = 0;
+    for seed in 0..32 {
+        if search(seed, false) == PASS {
+            pass_cnt += 1;
+            assert_eq!(search(seed, true), xy2ev(7, 5));
+        }
+    }
+    assert!(pass_cnt > 0);
 }
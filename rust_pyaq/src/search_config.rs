@@ -0,0 +1,251 @@
+use std::str::FromStr;
+
+/// 未訪問の子ノードの行動価値(First Play Urgency)の決め方です。
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Fpu {
+    /// 親ノードの平均価値を使います。
+    ParentValue,
+    /// 親ノードの平均価値から、訪問済みの子ノードの事前確率の和の平方根に比例する値を引きます。
+    Reduction(f32),
+    /// 負けとみなします。
+    Loss,
+    /// 勝ちとみなします。
+    Win,
+}
+
+impl Fpu {
+    /// 親ノードの平均価値parent_valueと訪問済みの子ノードの事前確率の和visited_probから、
+    /// 未訪問の子ノードの行動価値を返します。
+    pub fn value(&self, parent_value: f32, visited_prob: f32) -> f32 {
+        match *self {
+            Fpu::ParentValue => parent_value,
+            Fpu::Reduction(r) => parent_value - r * visited_prob.sqrt(),
+            Fpu::Loss => -1.0,
+            Fpu::Win => 1.0,
+        }
+    }
+}
+
+impl FromStr for Fpu {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut iter = s.splitn(2, ':');
+        match (iter.next(), iter.next()) {
+            (Some("parent"), None) => Ok(Fpu::ParentValue),
+            (Some("reduction"), Some(r)) => match r.parse::<f32>() {
+                Ok(r) if r >= 0.0 && r.is_finite() => Ok(Fpu::Reduction(r)),
+                _ => Err("invalid fpu reduction"),
+            },
+            (Some("loss"), None) => Ok(Fpu::Loss),
+            (Some("win"), None) => Ok(Fpu::Win),
+            _ => Err("unknown fpu"),
+        }
+    }
+}
+
+/// MCTSの探索パラメータです。
+/// Treeが所有し、コマンドライン引数やGTPのset_paramコマンドで変更できます。
+#[derive(Clone, Debug)]
+pub struct SearchConfig {
+    /// PUCTの探索係数です。
+    pub c_puct: f32,
+    /// 序盤に使う探索係数です。
+    pub c_puct_opening: f32,
+    /// c_puct_openingを使う手数です。
+    pub opening_moves: usize,
+    /// 子ノードを展開するのに必要な訪問回数です。
+    pub expand_cnt: usize,
+    /// ルートノードでの未訪問の子ノードの行動価値の決め方です。
+    pub fpu_root: Fpu,
+    /// ルート以外のノードでの未訪問の子ノードの行動価値の決め方です。
+    pub fpu: Fpu,
+    /// ルートの事前確率に混ぜるディリクレノイズの集中度です。
+    pub noise_alpha: f32,
+    /// ルートの事前確率に混ぜるディリクレノイズの割合です。0の場合はノイズを混ぜません。
+    pub noise_epsilon: f32,
+    /// 着手を訪問回数から確率的に選ぶ際の温度です。
+    pub temperature: f32,
+    /// temperatureで着手を選ぶ手数です。それ以降は最も訪問回数の多い着手を選びます。
+    pub temperature_moves: usize,
+    /// 最終的な着手を訪問回数ではなく価値の信頼下限で選ぶかどうかです。
+    pub lcb: bool,
+    /// 信頼下限を求める際に標準誤差の何倍を引くかです。
+    pub lcb_z: f32,
+    /// 信頼下限で選ぶ候補に必要な、最多訪問回数に対する訪問回数の割合です。
+    pub lcb_min_visit_ratio: f32,
+    /// 残りのプレイアウトで最善手が入れ替わらなくなった時に探索を打ち切るかどうかです。
+    pub early_stop: bool,
+}
+
+/// 設定可能なパラメータ名とその説明です。
+pub const PARAMS: [(&str, &str); 14] = [
+    ("c_puct", "PUCT exploration constant. default: 1.5"),
+    ("c_puct_opening", "PUCT exploration constant in the opening. default: 0.01"),
+    ("opening_moves", "Number of moves using c_puct_opening. default: 8"),
+    ("expand_cnt", "Visits needed to expand a child node. default: 8"),
+    (
+        "fpu_root",
+        "First play urgency at the root (parent|reduction:R|loss|win). default: parent",
+    ),
+    (
+        "fpu",
+        "First play urgency at interior nodes (parent|reduction:R|loss|win). default: parent",
+    ),
+    ("noise_alpha", "Dirichlet noise alpha at the root. default: 0.3"),
+    (
+        "noise_epsilon",
+        "Weight of Dirichlet noise at the root. default: 0 (0.25 for self play)",
+    ),
+    ("temperature", "Temperature of move selection by visits. default: 1"),
+    (
+        "temperature_moves",
+        "Number of moves selected with temperature. default: 0 (10 for self play)",
+    ),
+    ("lcb", "Select the final move by lower confidence bound (true|false). default: false"),
+    ("lcb_z", "Standard errors subtracted for the lower confidence bound. default: 1.96"),
+    (
+        "lcb_min_visit_ratio",
+        "Minimum visits relative to the most visited move for LCB selection. default: 0.1",
+    ),
+    (
+        "early_stop",
+        "Stop searching when the best move can no longer change (true|false). \
+         default: true (false for self play)",
+    ),
+];
+
+fn parse<T: FromStr>(value: &str) -> Result<T, &'static str> {
+    value.parse().map_err(|_| "invalid value")
+}
+
+/// 0以上の有限の実数を解釈します。NaNも受け付けません。
+fn parse_non_negative(value: &str) -> Result<f32, &'static str> {
+    let v: f32 = parse(value)?;
+    if v >= 0.0 && v.is_finite() {
+        Ok(v)
+    } else {
+        Err("must be a non-negative number")
+    }
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            c_puct: 1.5,
+            c_puct_opening: 0.01,
+            opening_moves: 8,
+            expand_cnt: 8,
+            fpu_root: Fpu::ParentValue,
+            fpu: Fpu::ParentValue,
+            noise_alpha: 0.3,
+            noise_epsilon: 0.0,
+            temperature: 1.0,
+            temperature_moves: 0,
+            lcb: false,
+            lcb_z: 1.96,
+            lcb_min_visit_ratio: 0.1,
+            early_stop: true,
+        }
+    }
+}
+
+impl SearchConfig {
+    /// 手数move_cntの局面での探索係数を返します。
+    pub fn c_puct_at(&self, move_cnt: usize) -> f32 {
+        if move_cnt < self.opening_moves {
+            self.c_puct_opening
+        } else {
+            self.c_puct
+        }
+    }
+
+    /// パラメータnameを文字列valueから設定します。
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), &'static str> {
+        match name {
+            "c_puct" => self.c_puct = parse_non_negative(value)?,
+            "c_puct_opening" => self.c_puct_opening = parse_non_negative(value)?,
+            "opening_moves" => self.opening_moves = parse(value)?,
+            "expand_cnt" => self.expand_cnt = parse(value)?,
+            "fpu_root" => self.fpu_root = value.parse()?,
+            "fpu" => self.fpu = value.parse()?,
+            "noise_alpha" => {
+                let alpha: f32 = parse(value)?;
+                if !(alpha > 0.0 && alpha.is_finite()) {
+                    return Err("noise_alpha must be positive");
+                }
+                self.noise_alpha = alpha;
+            }
+            "noise_epsilon" => {
+                let epsilon = parse(value)?;
+                if !(epsilon >= 0.0 && epsilon <= 1.0) {
+                    return Err("noise_epsilon must be between 0 and 1");
+                }
+                self.noise_epsilon = epsilon;
+            }
+            "temperature" => {
+                let temperature: f32 = parse(value)?;
+                if !(temperature > 0.0 && temperature.is_finite()) {
+                    return Err("temperature must be positive");
+                }
+                self.temperature = temperature;
+            }
+            "temperature_moves" => self.temperature_moves = parse(value)?,
+            "lcb" => self.lcb = parse(value)?,
+            "lcb_z" => self.lcb_z = parse_non_negative(value)?,
+            "lcb_min_visit_ratio" => {
+                let ratio = parse_non_negative(value)?;
+                if ratio > 1.0 {
+                    return Err("lcb_min_visit_ratio must be between 0 and 1");
+                }
+                self.lcb_min_visit_ratio = ratio;
+            }
+            "early_stop" => self.early_stop = parse(value)?,
+            _ => return Err("unknown parameter"),
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_fpu() {
+    assert_eq!("parent".parse(), Ok(Fpu::ParentValue));
+    assert_eq!("reduction:0.25".parse(), Ok(Fpu::Reduction(0.25)));
+    assert_eq!("loss".parse(), Ok(Fpu::Loss));
+    assert!("reduction".parse::<Fpu>().is_err());
+    assert!("reduction:-0.25".parse::<Fpu>().is_err());
+    assert!("reduction:NaN".parse::<Fpu>().is_err());
+    assert_eq!(Fpu::Reduction(0.5).value(0.2, 0.16), 0.0);
+    assert_eq!(Fpu::Win.value(0.2, 0.16), 1.0);
+}
+
+#[test]
+fn test_set() {
+    let mut config = SearchConfig::default();
+    assert!(config.set("c_puct", "0.8").is_ok());
+    assert_eq!(config.c_puct, 0.8);
+    assert!(config.set("expand_cnt", "x").is_err());
+    assert!(config.set("unknown", "1").is_err());
+    assert!(config.set("noise_epsilon", "1.5").is_err());
+    assert!(config.set("noise_epsilon", "-0.1").is_err());
+    assert_eq!(config.noise_epsilon, 0.0);
+    assert!(config.set("temperature", "0").is_err());
+    assert!(config.set("temperature", "-1").is_err());
+    assert!(config.set("temperature", "NaN").is_err());
+    assert_eq!(config.temperature, 1.0);
+    assert!(config.set("noise_alpha", "NaN").is_err());
+    assert!(config.set("noise_alpha", "-0.3").is_err());
+    assert!(config.set("noise_epsilon", "NaN").is_err());
+    assert!(config.set("c_puct", "NaN").is_err());
+    assert!(config.set("c_puct", "-1").is_err());
+    assert!(config.set("c_puct_opening", "inf").is_err());
+    assert_eq!(config.c_puct, 0.8);
+    assert!(config.set("fpu", "reduction:-1").is_err());
+    assert!(config.set("temperature_moves", "-1").is_err());
+    assert!(config.set("lcb_z", "-1.96").is_err());
+    assert!(config.set("lcb_min_visit_ratio", "1.5").is_err());
+    assert!(config.set("lcb_min_visit_ratio", "NaN").is_err());
+    for &(name, _) in PARAMS.iter() {
+        assert_ne!(config.set(name, "?"), Err("unknown parameter"));
+    }
+}
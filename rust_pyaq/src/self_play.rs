@@ -0,0 +1,218 @@
+use getopts;
+use rand::{self, Rng};
+use constants::*;
+use board::Board;
+use intersection::Color;
+use resign::ResignPolicy;
+use search::{Evaluate, Tree};
+use training_data::{one_hot, ChunkWriter, PositionRecord};
+use utils;
+use {add_engine_opts, parse_args, parse_opt, EngineSettings};
+
+/// 自己対戦の結果です。
+struct SelfPlayResult {
+    board: Board,
+    resigned: Option<Color>,     // 投了した手番
+    would_resign: Option<Color>, // 投了を無効にした対局で最初に投了の条件を満たした手番
+    records: Vec<PositionRecord>, // 学習データのための各局面の記録
+}
+
+impl SelfPlayResult {
+    /// 勝者を返します。投了していなければロールアウトで終局図を判定します。持碁の場合はNoneです。
+    fn winner<R: Rng>(&self, rng: &mut R) -> (Option<Color>, String) {
+        if let Some(color) = self.resigned {
+            let winner = color.opponent();
+            return (Some(winner), format!("{}+R", color2str(winner)));
+        }
+        let score = self.board.final_score(rng);
+        if score == 0.0 {
+            (None, "Draw".to_string())
+        } else {
+            let winner = if score > 0.0 {
+                Color::Black
+            } else {
+                Color::White
+            };
+            (Some(winner), format!("{}+{:.1}", color2str(winner), score.abs()))
+        }
+    }
+}
+
+fn color2str(color: Color) -> &'static str {
+    match color {
+        Color::Black => "B",
+        Color::White => "W",
+    }
+}
+
+fn random_self_play<R: Rng>(max_move_cnt: usize, rng: &mut R) -> SelfPlayResult {
+    let mut b = Board::new();
+    while b.get_move_cnt() < max_move_cnt {
+        let prev_move = b.get_prev_move();
+        let mov = b.random_play(rng);
+        let _ = b.play(mov, false);
+        debug!(target: "board", "{}\n", b.to_diagram());
+        if prev_move == PASS && mov == PASS {
+            break;
+        }
+    }
+    SelfPlayResult {
+        board: b,
+        resigned: None,
+        would_resign: None,
+        records: Vec::new(),
+    }
+}
+
+/// 自己対戦を1局行います。resign_disabledの時は投了せず、投了の条件を満たした手番を記録します。
+fn self_play<T: Evaluate>(
+    tree: &mut Tree<T>,
+    max_move_cnt: usize,
+    time: f32,
+    clean: bool,
+    resign: &mut ResignPolicy,
+    resign_disabled: bool,
+) -> SelfPlayResult {
+    let mut b = Board::new();
+    let mut resigned = None;
+    let mut would_resign = None;
+    let mut records = Vec::new();
+    tree.clear();
+    resign.clear();
+    while b.get_move_cnt() < max_move_cnt {
+        let prev_move = b.get_prev_move();
+        let (mov, win_rate) = tree.search(&b, time, false, clean);
+        if resign.should_resign(b.get_move_cnt(), win_rate) {
+            let color = if b.get_move_cnt() % 2 == 0 {
+                Color::Black
+            } else {
+                Color::White
+            };
+            if !resign_disabled {
+                resigned = Some(color);
+                break;
+            }
+            if would_resign.is_none() {
+                would_resign = Some(color);
+            }
+        }
+        let policy = tree.root_policy().unwrap_or_else(|| one_hot(mov));
+        records.push(PositionRecord::new(&b, policy));
+        let _ = b.play(mov, false);
+        debug!(target: "board", "{}\n", b.to_diagram());
+        if prev_move == PASS && mov == PASS {
+            break;
+        }
+    }
+    SelfPlayResult {
+        board: b,
+        resigned: resigned,
+        would_resign: would_resign,
+        records: records,
+    }
+}
+
+/// selfplayサブコマンドを実行します。
+pub fn selfplay_main(program: &str, args: &[String]) {
+    let mut opts = getopts::Options::new();
+    opts.optflag("h", "help", "Help")
+        .optflag("", "random", "Random play.")
+        .optflag("", "clean", "Try to pickup all dead stones.")
+        .optopt("", "games", "Number of self play games. default: 1", "NUM")
+        .optopt(
+            "",
+            "data_dir",
+            "Directory to write self play training data chunks.",
+            "DIR",
+        )
+        .optopt("", "chunk_size", "Samples per training data chunk. default: 4096", "NUM")
+        .optopt(
+            "",
+            "resign_disable_ratio",
+            "Ratio of self play games with resignation disabled to check false resignations. \
+             default: 0.1",
+            "NUM",
+        );
+    add_engine_opts(&mut opts);
+    let matches = parse_args(&opts, args, &format!("Usage: {} selfplay [options]", program));
+    let random = matches.opt_present("random");
+    let clean = matches.opt_present("clean");
+    let mut settings = EngineSettings::new(&matches, true);
+    if !random {
+        settings.check_model();
+    }
+    let mut rng = match settings.seed {
+        Some(seed) => utils::seeded_rng(seed),
+        None => rand::weak_rng(),
+    };
+    let games = parse_opt(&matches, "games", 1);
+    let resign_disable_ratio = parse_opt(&matches, "resign_disable_ratio", 0.1);
+    let mut tree = if random { None } else { Some(settings.make_tree()) };
+    let mut checked_cnt = 0;
+    let mut false_resign_cnt = 0;
+    let mut writer = matches.opt_str("data_dir").map(|dir| {
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let prefix = format!("selfplay_{}", now.as_secs());
+        let chunk_size = parse_opt(&matches, "chunk_size", 4096);
+        ChunkWriter::new(&dir, &prefix, chunk_size).unwrap_or_else(|e| {
+            eprintln!("--data_dir: {}", e);
+            std::process::exit(1);
+        })
+    });
+
+    for _ in 0..games {
+        let resign_disabled = rng.gen::<f32>() < resign_disable_ratio;
+        let result = match tree {
+            Some(ref mut tree) => self_play(
+                tree,
+                BVCNT * 2,
+                0.0,
+                clean,
+                &mut settings.resign,
+                resign_disabled,
+            ),
+            None => random_self_play(BVCNT * 2, &mut rng),
+        };
+
+        let (winner, result_str) = result.winner(&mut rng);
+        info!(target: "selfplay", "result: {}", result_str);
+        if let Some(ref mut writer) = writer {
+            if let Err(e) = writer.add_game(result.records, winner) {
+                eprintln!("failed to write training data: {}", e);
+                std::process::exit(1);
+            }
+        }
+        if let Some(color) = result.would_resign {
+            // 投了していたら負けになっていたはずの手番が負けていなければ誤った投了
+            checked_cnt += 1;
+            let false_resign = winner != Some(color.opponent());
+            if false_resign {
+                false_resign_cnt += 1;
+            }
+            info!(
+                target: "selfplay",
+                "resignation by {} would have been {}",
+                color2str(color),
+                if false_resign { "false" } else { "correct" }
+            );
+        }
+    }
+    if let Some(mut writer) = writer {
+        if let Err(e) = writer.flush() {
+            eprintln!("failed to write training data: {}", e);
+            std::process::exit(1);
+        }
+        info!(target: "selfplay", "training data chunks: {}", writer.get_chunk_cnt());
+    }
+    if checked_cnt > 0 {
+        info!(
+            target: "selfplay",
+            "false resignations: {}/{} ({:.1}%)",
+            false_resign_cnt,
+            checked_cnt,
+            false_resign_cnt as f32 / checked_cnt as f32 * 100.0
+        );
+    }
+}
@@ -0,0 +1,125 @@
+use constants::*;
+use coord_convert::ev2xy;
+
+/// SGFのテキストの値の中で特別な意味を持つ文字をエスケープします。
+fn escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c == ']' || c == '\\' {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+    result
+}
+
+/// 着手ev(拡張碁盤の線形座標)をSGFの座標に変換します。パスは空文字列です。
+pub fn ev2sgf(ev: usize) -> String {
+    if ev >= PASS {
+        return String::new();
+    }
+    let (x, y) = ev2xy(ev);
+    let offset = 'a' as u8 - 1;
+    [(x + offset) as char, (y + offset) as char].iter().collect()
+}
+
+/// 着手につける注釈です。
+#[derive(Clone, Debug, Default)]
+pub struct Annotation {
+    /// コメント(Cプロパティ)です。空の場合は出力しません。
+    pub comment: String,
+    /// 悪手の印(BMプロパティ)をつけるかどうかです。
+    pub bad_move: bool,
+    /// この着手の代わりの手順です。変化として出力します。
+    pub variation: Vec<usize>,
+}
+
+/// i手目の着手movのノードを返します。
+fn move_node(i: usize, mov: usize, annotation: Option<&Annotation>) -> String {
+    let color = if i % 2 == 0 { 'B' } else { 'W' };
+    let mut node = format!("\n;{}[{}]", color, ev2sgf(mov));
+    if let Some(annotation) = annotation {
+        if !annotation.comment.is_empty() {
+            node.push_str(&format!("C[{}]", escape(&annotation.comment)));
+        }
+        if annotation.bad_move {
+            node.push_str("BM[1]");
+        }
+    }
+    node
+}
+
+/// 初期局面からの着手の列historyを、対局者名black, whiteと結果resultとともにSGFに変換します。
+/// resultが空の場合はREプロパティを出力しません。
+pub fn to_sgf(history: &[usize], black: &str, white: &str, result: &str) -> String {
+    to_annotated_sgf(history, black, white, result, &[])
+}
+
+/// to_sgfと同様ですが、i手目にannotations[i]の注釈をつけます。
+pub fn to_annotated_sgf(
+    history: &[usize],
+    black: &str,
+    white: &str,
+    result: &str,
+    annotations: &[Annotation],
+) -> String {
+    let mut sgf = format!(
+        "(;GM[1]FF[4]CA[UTF-8]AP[rsaq]SZ[{}]KM[{}]RU[Chinese]PB[{}]PW[{}]",
+        BSIZE,
+        KOMI,
+        escape(black),
+        escape(white)
+    );
+    if !result.is_empty() {
+        sgf.push_str(&format!("RE[{}]", escape(result)));
+    }
+    // 変化は入れ子になるので、後ろの手から組み立てる
+    let mut tail = String::new();
+    for (i, &mov) in history.iter().enumerate().rev() {
+        let annotation = annotations.get(i);
+        let main = move_node(i, mov, annotation) + &tail;
+        tail = match annotation {
+            Some(a) if !a.variation.is_empty() => {
+                let variation: String = a.variation
+                    .iter()
+                    .enumerate()
+                    .map(|(j, &v)| move_node(i + j, v, None))
+                    .collect();
+                format!("\n({})\n({})", main, variation)
+            }
+            _ => main,
+        };
+    }
+    sgf.push_str(&tail);
+    sgf.push_str(")\n");
+    sgf
+}
+
+#[test]
+fn test_to_sgf() {
+    use coord_convert::xy2ev;
+
+    assert_eq!(ev2sgf(xy2ev(1, 2)), "ab");
+    assert_eq!(ev2sgf(PASS), "");
+    let sgf = to_sgf(&[xy2ev(3, 3), PASS], "a]b", "w", "B+R");
+    assert!(sgf.contains("PB[a\\]b]PW[w]RE[B+R]"));
+    assert!(sgf.ends_with("\n;B[cc]\n;W[])\n"));
+}
+
+#[test]
+fn test_to_annotated_sgf() {
+    use coord_convert::xy2ev;
+
+    let annotations = vec![
+        Annotation::default(),
+        Annotation {
+            comment: "best: C4".to_string(),
+            bad_move: true,
+            variation: vec![xy2ev(3, 4), xy2ev(4, 4)],
+        },
+    ];
+    let history = [xy2ev(3, 3), xy2ev(4, 3), xy2ev(5, 5)];
+    let sgf = to_annotated_sgf(&history, "b", "w", "", &annotations);
+    let expected = "\n;B[cc]\n(\n;W[dc]C[best: C4]BM[1]\n;B[ee])\n(\n;W[cd]\n;B[dd]))\n";
+    assert!(sgf.ends_with(expected));
+}
@@ -36,6 +36,11 @@ impl StoneGroup {
         self.v_atr
     }
 
+    #[inline]
+    pub fn get_libs(&self) -> &HashSet<usize> {
+        &self.libs
+    }
+
     pub fn clear(&mut self, stone: bool) {
         self.lib_cnt = if stone { 0 } else { VNULL };
         self.size = if stone { 1 } else { VNULL };
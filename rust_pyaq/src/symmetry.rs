@@ -0,0 +1,166 @@
+//! 碁盤の8通りの対称変換(二面体群)を提供します。
+//! 対称変換は0から7の整数で表し、ビット2が対角線での反転、ビット0がx方向の反転、
+//! ビット1がy方向の反転で、この順に適用します。0は恒等変換です。
+
+use constants::*;
+use coord_convert::*;
+use intersection::Intersection;
+use board::Board;
+
+/// 対称変換の数です。
+pub const SYMMETRY_CNT: usize = 8;
+
+/// 対称変換symの逆変換を返します。
+/// 対角線での反転を含む場合、x方向とy方向の反転が入れ替わります。
+pub fn inverse(sym: usize) -> usize {
+    if sym & 4 != 0 {
+        4 | (sym & 1) << 1 | (sym & 2) >> 1
+    } else {
+        sym
+    }
+}
+
+/// 碁盤の線形座標rvを対称変換symで移した座標を返します。パスはパスのままです。
+pub fn transform_rv(rv: usize, sym: usize) -> usize {
+    if rv == BVCNT {
+        return rv;
+    }
+    let (mut x, mut y) = (rv % BSIZE, rv / BSIZE);
+    if sym & 4 != 0 {
+        let tmp = x;
+        x = y;
+        y = tmp;
+    }
+    if sym & 1 != 0 {
+        x = BSIZE - 1 - x;
+    }
+    if sym & 2 != 0 {
+        y = BSIZE - 1 - y;
+    }
+    x + y * BSIZE
+}
+
+/// 拡張碁盤の線形座標evを対称変換symで移した座標を返します。パスはパスのままです。
+pub fn transform_ev(ev: usize, sym: usize) -> usize {
+    rv2ev(transform_rv(ev2rv(ev), sym))
+}
+
+/// ニューラルネットワークへの入力featuresを対称変換symで移したものを返します。
+/// 並びはBoard::put_featuresと同じです。
+pub fn transform_features(features: &[f32], sym: usize) -> Vec<f32> {
+    let mut result = vec![0.0; BVCNT * FEATURE_CNT];
+    for rv in 0..BVCNT {
+        let dest = transform_rv(rv, sym) * FEATURE_CNT;
+        let src = rv * FEATURE_CNT;
+        result[dest..dest + FEATURE_CNT].copy_from_slice(&features[src..src + FEATURE_CNT]);
+    }
+    result
+}
+
+/// 方策policy(碁盤の線形座標の順で最後がパス)を対称変換symで移したものを返します。
+pub fn transform_policy(policy: &[f32], sym: usize) -> Vec<f32> {
+    let mut result = vec![0.0; BVCNT + 1];
+    for rv in 0..BVCNT + 1 {
+        result[transform_rv(rv, sym)] = policy[rv];
+    }
+    result
+}
+
+/// 局面bを対称変換symで移した局面を返します。
+/// 盤上の状態と直前の局面、コウの点、着手の履歴を移すので、履歴のない局面も移せます。
+pub fn transform_board(b: &Board, sym: usize) -> Board {
+    let transform_v = |v: usize| if v == VNULL { v } else { transform_ev(v, sym) };
+    let mut state = [Intersection::Exterior; EBVCNT];
+    let mut prev_state = [[Intersection::Exterior; EBVCNT]; KEEP_PREV_CNT];
+    for rv in 0..BVCNT {
+        let (v, dest) = (rv2ev(rv), rv2ev(transform_rv(rv, sym)));
+        state[dest] = b.get_state(v);
+        for (i, e) in prev_state.iter_mut().enumerate() {
+            e[dest] = b.get_prev_state(i, v);
+        }
+    }
+    let history = b.get_history().iter().map(|&v| transform_v(v)).collect();
+    // 合法な局面を対称変換した局面も合法なので、エラーにはならない
+    Board::from_raw(&state, &prev_state, transform_v(b.get_ko()), b.get_turn(), history)
+        .unwrap()
+}
+
+#[test]
+fn test_transform_rv() {
+    assert_eq!(transform_rv(0, 0), 0);
+    assert_eq!(transform_rv(0, 1), BSIZE - 1);
+    assert_eq!(transform_rv(0, 3), BVCNT - 1);
+    assert_eq!(transform_rv(1, 4), BSIZE);
+    assert_eq!(transform_rv(BVCNT, 5), BVCNT);
+}
+
+#[test]
+fn test_inverse() {
+    for sym in 0..SYMMETRY_CNT {
+        assert_eq!(inverse(inverse(sym)), sym);
+        for rv in 0..BVCNT + 1 {
+            assert_eq!(transform_rv(transform_rv(rv, sym), inverse(sym)), rv);
+        }
+        assert_eq!(transform_ev(transform_ev(PASS, sym), inverse(sym)), PASS);
+        let ev = xy2ev(2, 3);
+        assert_eq!(transform_ev(transform_ev(ev, sym), inverse(sym)), ev);
+    }
+}
+
+#[test]
+fn test_transform_board() {
+    let mut b = Board::new();
+    b.play_sequence([xy2ev(3, 3), xy2ev(4, 3), PASS, xy2ev(5, 7)].iter().cloned());
+    let mut features = vec![0.0; BVCNT * FEATURE_CNT];
+    b.put_features(&mut features);
+    let policy: Vec<f32> = (0..BVCNT + 1).map(|i| i as f32).collect();
+    for sym in 0..SYMMETRY_CNT {
+        let tb = transform_board(&b, sym);
+        let mut t_features = vec![0.0; BVCNT * FEATURE_CNT];
+        tb.put_features(&mut t_features);
+        assert_eq!(t_features, transform_features(&features, sym));
+        assert_eq!(tb.get_prev_move(), transform_ev(b.get_prev_move(), sym));
+
+        let restored = transform_board(&tb, inverse(sym));
+        assert_eq!(restored.get_history(), b.get_history());
+        let back = transform_features(&t_features, inverse(sym));
+        assert_eq!(back, features);
+        let t_policy = transform_policy(&policy, sym);
+        assert_eq!(transform_policy(&t_policy, inverse(sym)), policy);
+    }
+}
+
+#[test]
+fn test_transform_diagram() {
+    use intersection::Color;
+
+    let diagram = "
+        . . . . . . . . .
+        . . . . . . . . .
+        . . X O . . . . .
+        . X O . O . . . .
+        . . X O . . . . .
+        . . . X . . . . .
+        . . . . . . . . .
+        . . . . . . . . .
+        . . . . . . . . .
+    ";
+    let b = Board::from_diagram(diagram, Color::Black).unwrap();
+    let mut features = vec![0.0; BVCNT * FEATURE_CNT];
+    b.put_features(&mut features);
+    for sym in 0..SYMMETRY_CNT {
+        let tb = transform_board(&b, sym);
+        for rv in 0..BVCNT {
+            assert!(tb.get_state(rv2ev(transform_rv(rv, sym))) == b.get_state(rv2ev(rv)));
+        }
+        assert!(tb.get_turn() == Color::Black);
+        assert_eq!(tb.get_move_cnt(), 0);
+        let mut t_features = vec![0.0; BVCNT * FEATURE_CNT];
+        tb.put_features(&mut t_features);
+        assert_eq!(t_features, transform_features(&features, sym));
+        // 連も作り直されているので、同じ石を取る手が合法手になる
+        let capture = transform_ev(xy2ev(4, 6), sym);
+        assert!(tb.legal(capture));
+        assert_eq!(tb.tactics(capture).capture_cnt, 1);
+    }
+}
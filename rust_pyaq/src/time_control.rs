@@ -0,0 +1,268 @@
+/// 探索時間の下限です。
+const MIN_SEARCH_TIME: f32 = 0.1;
+
+/// 持ち時間を使い切った後の方式です。
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Overtime {
+    /// 秒読みなし(切れ負け)です。
+    Absolute,
+    /// 日本式の秒読みです。time秒以内に打てば秒読みが減らず、超えるとperiodsが1減ります。
+    Byoyomi { time: f32, periods: usize },
+    /// カナダ式の秒読みです。time秒の間にstones手を打ちます。
+    Canadian { time: f32, stones: usize },
+}
+
+/// 持ち時間を管理し、1手あたりの探索時間を決めます。
+#[derive(Clone, Debug)]
+pub struct TimeManager {
+    main_time: f32,
+    overtime: Overtime,
+    left_time: f32,     // 持ち時間中は残りの持ち時間、秒読み中は現在の秒読みの残り時間
+    left_stones: usize, // 秒読み中の残り回数(日本式)または残り手数(カナダ式)。持ち時間中は0
+    saved_time: f32,    // 探索を早く打ち切って余った時間
+    /// ネットワークの遅延などに備えて探索時間から差し引く時間です。
+    pub lag: f32,
+    /// 最善手が不安定な時に延長できる、割り当て時間に対する割合です。
+    pub max_extension: f32,
+    /// 探索を早く打ち切って余った時間を次の手以降に上乗せするかどうかです。
+    pub reallocate: bool,
+}
+
+impl TimeManager {
+    pub fn new() -> Self {
+        Self {
+            main_time: 0.0,
+            overtime: Overtime::Byoyomi {
+                time: 1.0,
+                periods: 1,
+            },
+            left_time: 0.0,
+            left_stones: 0,
+            saved_time: 0.0,
+            lag: 0.0,
+            max_extension: 1.0,
+            reallocate: false,
+        }
+    }
+
+    /// 持ち時間main_timeと秒読みの方式overtimeを設定します。
+    pub fn set_time_settings(&mut self, main_time: f32, overtime: Overtime) {
+        self.main_time = main_time;
+        self.overtime = overtime;
+        self.reset();
+    }
+
+    /// GTPのtime_settingsの引数から設定します。
+    /// 秒読みの手数が0で秒読み時間が正の場合、GTPの仕様では時間無制限ですが、
+    /// 秒読み時間を1手の思考時間として使います。
+    pub fn set_gtp_time_settings(&mut self, main_time: f32, byoyomi: f32, stones: usize) {
+        let overtime = if stones > 0 {
+            Overtime::Canadian {
+                time: byoyomi,
+                stones: stones,
+            }
+        } else if byoyomi > 0.0 {
+            Overtime::Byoyomi {
+                time: byoyomi,
+                periods: 1,
+            }
+        } else {
+            Overtime::Absolute
+        };
+        self.set_time_settings(main_time, overtime);
+    }
+
+    /// 対局開始時の状態に戻します。
+    pub fn reset(&mut self) {
+        self.left_time = self.main_time;
+        self.left_stones = 0;
+        self.saved_time = 0.0;
+    }
+
+    /// GTPのtime_leftの引数から残り時間を設定します。
+    /// stonesが0なら持ち時間中、正なら秒読み中の残り回数(日本式)または残り手数(カナダ式)です。
+    pub fn set_time_left(&mut self, time: f32, stones: usize) {
+        self.left_time = time;
+        self.left_stones = stones;
+    }
+
+    pub fn left_time(&self) -> f32 {
+        self.left_time
+    }
+
+    /// 秒読みで1手に使える時間を返します。
+    fn overtime_per_move(&self) -> f32 {
+        match self.overtime {
+            Overtime::Absolute => 0.0,
+            Overtime::Byoyomi { time, periods } => if periods > 0 {
+                time
+            } else {
+                0.0
+            },
+            Overtime::Canadian { time, stones } => if stones > 0 {
+                time / stones as f32
+            } else {
+                0.0
+            },
+        }
+    }
+
+    /// 手数move_cntの局面の探索時間を返します。
+    pub fn allocate(&self, move_cnt: usize) -> f32 {
+        let time = if self.left_stones > 0 {
+            match self.overtime {
+                Overtime::Canadian { .. } => self.left_time / self.left_stones as f32,
+                _ => self.left_time,
+            }
+        } else {
+            let overtime = self.overtime_per_move();
+            if overtime > 0.0 && self.left_time < overtime * 2.0 {
+                overtime
+            } else {
+                let main_share =
+                    self.left_time / (55.0 + 50usize.saturating_sub(move_cnt) as f32);
+                main_share.max(overtime) + self.saved_time * 0.5
+            }
+        };
+        (time - self.lag).max(MIN_SEARCH_TIME)
+    }
+
+    /// 最善手が不安定な時に延長できる探索時間の上限を返します。
+    pub fn max_time(&self, move_cnt: usize) -> f32 {
+        let time = self.allocate(move_cnt);
+        let limit = if self.left_stones > 0 {
+            match self.overtime {
+                // 残り手数の半分に割り当てるはずの時間まで
+                Overtime::Canadian { .. } => {
+                    self.left_time / ((self.left_stones + 1) / 2) as f32
+                }
+                _ => self.left_time,
+            }
+        } else {
+            (self.left_time * 0.5).max(self.overtime_per_move())
+        };
+        (time * (1.0 + self.max_extension))
+            .min(limit - self.lag)
+            .max(time)
+    }
+
+    /// 割り当てたallocated秒のうちelapsed秒しか使わなかったことを記録します。
+    /// reallocateの時、余った時間を次の手以降に上乗せします。上乗せは持ち時間中のみです。
+    pub fn save(&mut self, allocated: f32, elapsed: f32) {
+        if !self.reallocate || self.left_stones > 0 {
+            self.saved_time = 0.0;
+            return;
+        }
+        // 今回上乗せした分(saved_timeの半分)は使ったとみなし、余った分を戻す
+        self.saved_time = (self.saved_time * 0.5 + (allocated - elapsed).max(0.0))
+            .min(self.left_time * 0.25);
+    }
+
+    /// 1手でelapsed秒使ったことを記録します。
+    pub fn consume(&mut self, elapsed: f32) {
+        let mut elapsed = elapsed;
+        if self.left_stones == 0 {
+            if elapsed <= self.left_time || self.overtime == Overtime::Absolute {
+                self.left_time = (self.left_time - elapsed).max(0.0);
+                return;
+            }
+            elapsed -= self.left_time;
+            match self.overtime {
+                Overtime::Byoyomi { time, periods } => {
+                    self.left_time = time;
+                    self.left_stones = periods;
+                }
+                Overtime::Canadian { time, stones } => {
+                    self.left_time = time;
+                    self.left_stones = stones;
+                }
+                Overtime::Absolute => {}
+            }
+            if self.left_stones == 0 {
+                self.left_time = 0.0;
+                return;
+            }
+        }
+        match self.overtime {
+            Overtime::Byoyomi { time, .. } => {
+                while elapsed > time && self.left_stones > 1 {
+                    elapsed -= time;
+                    self.left_stones -= 1;
+                }
+                self.left_time = time;
+            }
+            Overtime::Canadian { time, stones } => {
+                self.left_time = (self.left_time - elapsed).max(0.0);
+                self.left_stones -= 1;
+                if self.left_stones == 0 {
+                    self.left_time = time;
+                    self.left_stones = stones;
+                }
+            }
+            Overtime::Absolute => {}
+        }
+    }
+}
+
+#[test]
+fn test_allocate() {
+    let mut tm = TimeManager::new();
+    assert_eq!(tm.allocate(0), 1.0);
+    tm.set_time_settings(600.0, Overtime::Absolute);
+    assert_eq!(tm.allocate(60), 600.0 / 55.0);
+    tm.set_time_settings(
+        0.0,
+        Overtime::Canadian {
+            time: 60.0,
+            stones: 10,
+        },
+    );
+    assert_eq!(tm.allocate(0), 6.0);
+    tm.set_time_left(10.0, 2);
+    assert_eq!(tm.allocate(0), 5.0);
+    assert_eq!(tm.max_time(0), 10.0);
+    tm.lag = 1.0;
+    assert_eq!(tm.allocate(0), 4.0);
+}
+
+#[test]
+fn test_save() {
+    let mut tm = TimeManager::new();
+    tm.set_time_settings(600.0, Overtime::Absolute);
+    let allocated = tm.allocate(60);
+    tm.save(allocated, 0.0);
+    assert_eq!(tm.allocate(60), allocated);
+    tm.reallocate = true;
+    tm.save(allocated, 0.0);
+    assert_eq!(tm.allocate(60), allocated * 1.5);
+}
+
+#[test]
+fn test_consume() {
+    let mut tm = TimeManager::new();
+    tm.set_time_settings(
+        10.0,
+        Overtime::Byoyomi {
+            time: 5.0,
+            periods: 3,
+        },
+    );
+    tm.consume(4.0);
+    assert_eq!((tm.left_time, tm.left_stones), (6.0, 0));
+    tm.consume(12.0); // 持ち時間を使い切り、秒読みを1回失う
+    assert_eq!((tm.left_time, tm.left_stones), (5.0, 2));
+    tm.consume(4.0);
+    assert_eq!((tm.left_time, tm.left_stones), (5.0, 2));
+
+    tm.set_time_settings(
+        0.0,
+        Overtime::Canadian {
+            time: 10.0,
+            stones: 2,
+        },
+    );
+    tm.consume(3.0);
+    assert_eq!((tm.left_time, tm.left_stones), (7.0, 1));
+    tm.consume(3.0);
+    assert_eq!((tm.left_time, tm.left_stones), (10.0, 2));
+}
@@ -0,0 +1,229 @@
+//! ニューラルネットワークの学習データの書き出しと読み込みを提供します。
+//!
+//! 学習データはチャンクファイルに分けて書き出します。
+//! チャンクファイルはgzipで圧縮されていて、展開すると以下のヘッダーとレコードの列です。
+//! 数値はすべてリトルエンディアンです。
+//!
+//! ヘッダー
+//!
+//! | フィールド | 型      | 内容                       |
+//! |------------|---------|----------------------------|
+//! | magic      | [u8; 4] | "RSAQ"                     |
+//! | version    | u32     | フォーマットのバージョン(1) |
+//! | count      | u32     | レコードの数               |
+//!
+//! レコード
+//!
+//! | フィールド | 型                        | 内容                                                                 |
+//! |------------|---------------------------|----------------------------------------------------------------------|
+//! | features   | [u8; BVCNT * FEATURE_CNT] | Board::put_featuresの値(0か1)。並びもput_featuresと同じ               |
+//! | policy     | [f32; BVCNT + 1]          | 方策の目標値。碁盤の線形座標の順で、最後がパス                       |
+//! | value      | i8                        | 着手する手番から見た対局結果。1: 勝ち、-1: 負け、0: 持碁             |
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use board::Board;
+use constants::*;
+use coord_convert::ev2rv;
+use intersection::Color;
+
+const MAGIC: &[u8; 4] = b"RSAQ";
+const VERSION: u32 = 1;
+
+/// 1局面分の学習データです。
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    pub features: Vec<f32>,
+    pub policy: Vec<f32>,
+    pub value: f32,
+}
+
+/// 対局中の1局面の記録です。対局結果は終局後に決まるので、手番を覚えておきます。
+pub struct PositionRecord {
+    pub features: Vec<f32>,
+    pub policy: Vec<f32>,
+    pub turn: Color,
+}
+
+impl PositionRecord {
+    /// 局面bと方策の目標値policyから記録を作成します。
+    pub fn new(b: &Board, policy: Vec<f32>) -> Self {
+        let mut features = vec![0.0; BVCNT * FEATURE_CNT];
+        b.put_features(&mut features);
+        Self {
+            features: features,
+            policy: policy,
+            turn: b.get_turn(),
+        }
+    }
+
+    /// 勝者winnerから学習データを作成します。持碁の場合winnerはNoneです。
+    pub fn into_sample(self, winner: Option<Color>) -> Sample {
+        let value = match winner {
+            Some(c) if c == self.turn => 1.0,
+            Some(_) => -1.0,
+            None => 0.0,
+        };
+        Sample {
+            features: self.features,
+            policy: self.policy,
+            value: value,
+        }
+    }
+}
+
+/// 着手mov(拡張碁盤の線形座標)だけが1の方策の目標値を返します。
+pub fn one_hot(mov: usize) -> Vec<f32> {
+    let mut policy = vec![0.0; BVCNT + 1];
+    policy[ev2rv(mov)] = 1.0;
+    policy
+}
+
+/// 学習データをチャンクファイルに分けて書き出すライターです。
+pub struct ChunkWriter {
+    dir: PathBuf,
+    prefix: String,
+    chunk_size: usize,
+    samples: Vec<Sample>,
+    chunk_cnt: usize,
+}
+
+impl ChunkWriter {
+    /// ディレクトリdirに、prefix_00000.gzのような名前でchunk_size個ずつ書き出すライターを作成します。
+    pub fn new<P: AsRef<Path>>(dir: P, prefix: &str, chunk_size: usize) -> io::Result<Self> {
+        fs::create_dir_all(dir.as_ref())?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+            prefix: prefix.to_string(),
+            chunk_size: chunk_size.max(1),
+            samples: Vec::with_capacity(chunk_size),
+            chunk_cnt: 0,
+        })
+    }
+
+    /// 1局分の局面の記録を勝者winnerとともに追加します。
+    pub fn add_game(&mut self, records: Vec<PositionRecord>, winner: Option<Color>) -> io::Result<()> {
+        for record in records {
+            self.push(record.into_sample(winner))?;
+        }
+        Ok(())
+    }
+
+    /// 学習データを追加し、チャンクの大きさに達したら書き出します。
+    pub fn push(&mut self, sample: Sample) -> io::Result<()> {
+        self.samples.push(sample);
+        if self.samples.len() >= self.chunk_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// 書き出していない学習データをチャンクファイルに書き出します。
+    pub fn flush(&mut self) -> io::Result<()> {
+        if self.samples.is_empty() {
+            return Ok(());
+        }
+        let path = self.dir
+            .join(format!("{}_{:05}.gz", self.prefix, self.chunk_cnt));
+        write_chunk(&path, &self.samples)?;
+        self.samples.clear();
+        self.chunk_cnt += 1;
+        Ok(())
+    }
+
+    /// 書き出したチャンクファイルの数を返します。
+    pub fn get_chunk_cnt(&self) -> usize {
+        self.chunk_cnt
+    }
+}
+
+impl Drop for ChunkWriter {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+fn write_u32<W: Write>(w: &mut W, v: u32) -> io::Result<()> {
+    w.write_all(&[v as u8, (v >> 8) as u8, (v >> 16) as u8, (v >> 24) as u8])
+}
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(buf[0] as u32 | (buf[1] as u32) << 8 | (buf[2] as u32) << 16 | (buf[3] as u32) << 24)
+}
+
+/// samplesをチャンクファイルpathに書き出します。
+pub fn write_chunk<P: AsRef<Path>>(path: P, samples: &[Sample]) -> io::Result<()> {
+    let mut encoder = GzEncoder::new(File::create(path)?, Compression::default());
+    encoder.write_all(MAGIC)?;
+    write_u32(&mut encoder, VERSION)?;
+    write_u32(&mut encoder, samples.len() as u32)?;
+    for sample in samples {
+        let features: Vec<u8> = sample.features.iter().map(|&f| f as u8).collect();
+        encoder.write_all(&features)?;
+        for &p in &sample.policy {
+            write_u32(&mut encoder, p.to_bits())?;
+        }
+        encoder.write_all(&[sample.value as i8 as u8])?;
+    }
+    encoder.finish()?;
+    Ok(())
+}
+
+/// チャンクファイルpathを読み込みます。
+pub fn read_chunk<P: AsRef<Path>>(path: P) -> io::Result<Vec<Sample>> {
+    let mut decoder = GzDecoder::new(File::open(path)?);
+    let mut magic = [0; 4];
+    decoder.read_exact(&mut magic)?;
+    if &magic != MAGIC || read_u32(&mut decoder)? != VERSION {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a chunk file"));
+    }
+    let count = read_u32(&mut decoder)? as usize;
+    let mut samples = Vec::with_capacity(count);
+    for _ in 0..count {
+        let mut features = vec![0; BVCNT * FEATURE_CNT];
+        decoder.read_exact(&mut features)?;
+        let mut policy = Vec::with_capacity(BVCNT + 1);
+        for _ in 0..BVCNT + 1 {
+            policy.push(f32::from_bits(read_u32(&mut decoder)?));
+        }
+        let mut value = [0; 1];
+        decoder.read_exact(&mut value)?;
+        samples.push(Sample {
+            features: features.into_iter().map(|f| f as f32).collect(),
+            policy: policy,
+            value: value[0] as i8 as f32,
+        });
+    }
+    Ok(samples)
+}
+
+#[test]
+fn test_chunk_round_trip() {
+    use std::env;
+
+    let b = Board::new();
+    let records = vec![
+        PositionRecord::new(&b, one_hot(PASS)),
+        PositionRecord::new(&b, vec![1.0 / (BVCNT + 1) as f32; BVCNT + 1]),
+    ];
+    let dir = env::temp_dir().join("rust_pyaq_test_chunk_round_trip");
+    {
+        let mut writer = ChunkWriter::new(&dir, "test", 16).unwrap();
+        writer.add_game(records, Some(Color::Black)).unwrap();
+    }
+    let samples = read_chunk(dir.join("test_00000.gz")).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(samples.len(), 2);
+    assert_eq!(samples[0].policy, one_hot(PASS));
+    assert_eq!(samples[0].value, 1.0);
+    let mut features = vec![0.0; BVCNT * FEATURE_CNT];
+    b.put_features(&mut features);
+    assert_eq!(samples[1].features, features);
+}
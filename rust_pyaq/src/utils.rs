@@ -1,4 +1,6 @@
-use std::hash::Hash;
+use rand::{SeedableRng, XorShiftRng};
+#[cfg(not(target_arch = "wasm32"))]
+use rand;
 
 /// 配列を指定の値vで埋めます。
 pub fn fill<T: Copy>(array: &mut [T], v: T) {
@@ -15,11 +17,12 @@ fn test_fill() {
 }
 
 /// 配列の中で最も出現頻度の高い要素を返します。
+/// 出現頻度が同じ要素が複数あれば最小の要素を返します。
 /// 空の配列を渡すとpanicします。
-pub fn most_common<T: Hash + Eq>(array: &[T]) -> &T {
-    use std::collections::HashMap;
+pub fn most_common<T: Ord>(array: &[T]) -> &T {
+    use std::collections::BTreeMap;
 
-    let mut map = HashMap::new();
+    let mut map = BTreeMap::new();
     for e in array {
         let counter = map.entry(e).or_insert(0);
         *counter += 1;
@@ -32,4 +35,53 @@ pub fn most_common<T: Hash + Eq>(array: &[T]) -> &T {
 #[test]
 fn test_most_common() {
     assert_eq!(most_common(&[0, 1, 1]), &1);
+    assert_eq!(most_common(&[3, 2, 1, 3, 1, 2]), &1);
+}
+
+/// SplitMix64で状態stateを進め、64ビットの値を返します。
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// シードseedから再現性のある乱数生成器を作成します。
+/// 同じシードからは常に同じ乱数列が得られます。
+/// シードをSplitMix64でかき混ぜるので、seedとseed + 1のような近いシードの乱数列も相関しません。
+pub fn seeded_rng(seed: u64) -> XorShiftRng {
+    let mut state = seed;
+    let a = splitmix64(&mut state);
+    let b = splitmix64(&mut state);
+    // XorShiftRngはシードがすべて0だとpanicするので、最後の要素を奇数にします。
+    XorShiftRng::from_seed([a as u32, (a >> 32) as u32, b as u32, (b >> 32) as u32 | 1])
+}
+
+/// OSの乱数でシードした乱数生成器を作成します。
+/// wasmではOSの乱数が使えないので、固定のシードを使います。
+#[cfg(not(target_arch = "wasm32"))]
+pub fn entropy_rng() -> XorShiftRng {
+    rand::weak_rng()
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn entropy_rng() -> XorShiftRng {
+    seeded_rng(0)
+}
+
+#[test]
+fn test_seeded_rng() {
+    use rand::Rng;
+
+    let a: Vec<u32> = seeded_rng(0).gen_iter().take(8).collect();
+    let b: Vec<u32> = seeded_rng(0).gen_iter().take(8).collect();
+    let c: Vec<u32> = seeded_rng(1).gen_iter().take(8).collect();
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+    // 近いシードでも最初の値から散らばる
+    let low = (0..32)
+        .filter(|&seed| seeded_rng(seed).gen::<f64>() < 0.5)
+        .count();
+    assert!(low > 4 && low < 28);
 }